@@ -1,17 +1,393 @@
-use clap::Parser;
+use crate::charset::TextEncoding;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// Location of the Paradox DB files
-    #[arg(short, long)]
-    pub location: String,
+    /// Read options from this TOML file; flags given on the command line
+    /// take precedence over it
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
 
-    /// Optional port if running SSE (not implemented, stdio by default)
-    #[arg(short, long)]
+    /// Directory of the Paradox DB files (required, here or in `--config`).
+    /// Repeat it, or join directories with the platform's path separator
+    /// (`:`, or `;` on Windows), to serve several: a table name opens the
+    /// first directory's table of that name, and new tables go in the first
+    #[arg(short, long, value_name = "DIR")]
+    pub location: Vec<String>,
+
+    /// Serve the MCP SSE transport on this port (on 127.0.0.1) instead of
+    /// stdio
+    #[arg(long)]
     pub port: Option<u16>,
 
     /// Permit editing the database
     #[arg(short, long, default_value_t = false)]
     pub permit_editing: bool,
+
+    /// Emit only the JSON content block from data tools, without the
+    /// human-readable summary text
+    #[arg(long, alias = "machine-output", default_value_t = false)]
+    pub json_output_only: bool,
+
+    /// Treat schema irregularities, such as a record size that doesn't match
+    /// the summed field lengths, as errors instead of warnings
+    #[arg(long, default_value_t = false)]
+    pub strict_types: bool,
+
+    /// How long a tool call waits for a table held by another call before
+    /// failing with TABLE_BUSY
+    #[arg(long, default_value_t = 5000)]
+    pub lock_timeout_ms: u64,
+
+    /// Lifetime of `paradox://result/...` links returned for
+    /// `as_resource_link` tool calls
+    #[arg(long, default_value_t = 600)]
+    pub resource_link_ttl_secs: u64,
+
+    /// Keep up to this many read handles open between tool calls, so
+    /// repeated reads skip reopening the table; cached tables stay open
+    /// (and so locked on some platforms) while idle. 0 disables the cache
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub handle_cache_size: usize,
+
+    /// Close cached read handles this long after they were opened
+    #[arg(long, default_value_t = 30)]
+    pub handle_cache_ttl_secs: u64,
+
+    /// Write-protect a field even when editing is permitted (repeatable),
+    /// e.g. `--readonly-field customers.ID`
+    #[arg(long = "readonly-field", value_name = "TABLE.FIELD")]
+    pub readonly_fields: Vec<String>,
+
+    /// What to do when a write touches a write-protected field
+    #[arg(long, value_enum, default_value_t = ReadonlyFieldMode::Reject)]
+    pub readonly_field_mode: ReadonlyFieldMode,
+
+    /// Key order of record objects returned by data tools: `alpha`,
+    /// `schema`, or a comma-separated list of field names to put first
+    #[arg(long, default_value = "alpha", value_name = "alpha|schema|FIELD,...")]
+    pub response_field_order: FieldOrder,
+
+    /// Render LONG and AUTOINC values outside JavaScript's safe integer range
+    /// (2^53) as strings so JS-based clients don't round them
+    #[arg(long, default_value_t = false)]
+    pub big_int_as_string: bool,
+
+    /// Render NUMBER values with no fractional part as JSON integers
+    /// (`123` instead of `123.0`) in results and CSV exports
+    #[arg(long, default_value_t = false)]
+    pub integral_numbers_as_int: bool,
+
+    /// Representation of DATE, TIME and TIMESTAMP values in results and of
+    /// numeric TIME input: `iso` ("2024-03-01", "14:30:00.000",
+    /// "2024-03-01T14:30:00Z"), `raw` (the stored Paradox value) or
+    /// `seconds` (Unix seconds for dates and timestamps, seconds since
+    /// midnight for times)
+    #[arg(long, value_enum, default_value_t = DateFormat::Iso)]
+    pub date_format: DateFormat,
+
+    /// Charset of ALPHA fields: `utf8`, a codepage such as `cp1252` or
+    /// `cp850`, an encoding label such as `iso-8859-2`, or `auto` to use
+    /// each table's stored codepage (falling back to UTF-8)
+    #[arg(long, default_value = "utf8", value_name = "ENCODING")]
+    pub encoding: TextEncoding,
+
+    /// Most verbose messages written to stderr: `off`, `error`, `warn`,
+    /// `info`, `debug`, or `trace` to include request and response bodies
+    #[arg(long, value_name = "LEVEL", default_value_t = log::LevelFilter::Warn)]
+    pub log_level: log::LevelFilter,
+
+    /// Write only errors to stderr, whatever `--log-level` or a client's
+    /// `logging/setLevel` asks for
+    #[arg(short, long, default_value_t = false, conflicts_with = "log_level")]
+    pub quiet: bool,
+
+    /// Records `read_table_data` and `read_sorted` return when a call gives
+    /// no `limit`
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 100,
+        value_parser = clap::value_parser!(i32).range(1..)
+    )]
+    pub default_limit: i32,
+
+    /// Largest `limit` `read_table_data` and `read_sorted` honor; larger
+    /// requests are cut down to it
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 10_000,
+        value_parser = clap::value_parser!(i32).range(1..)
+    )]
+    pub max_limit: i32,
+
+    /// Smallest ALPHA field length `create_table` accepts
+    #[arg(long, default_value_t = 1)]
+    pub min_alpha_length: u32,
+
+    /// Largest ALPHA field length `create_table` accepts
+    #[arg(long, default_value_t = 255)]
+    pub max_alpha_length: u32,
+
+    /// Longest BLOB or BYTES content returned per field, in bytes; longer
+    /// values are cut and flagged with `<field>_truncated`
+    #[arg(long, value_name = "BYTES", default_value_t = 1024 * 1024)]
+    pub max_blob_bytes: usize,
+
+    /// Examine at most this many records per `search_table` call; the
+    /// response reports where the scan stopped (default: no limit)
+    #[arg(long, value_name = "N")]
+    pub max_scan_records: Option<usize>,
+
+    /// Split each `search_table` scan across this many threads, each with
+    /// its own handle on the table; 1 scans on the calling thread
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub search_threads: usize,
+
+    /// Hide and refuse a tool (repeatable)
+    #[arg(long = "deny-tool", value_name = "TOOL")]
+    pub deny_tools: Vec<String>,
+
+    /// Expose only the listed tools (repeatable); `--deny-tool` still applies
+    #[arg(long = "allow-tool", value_name = "TOOL")]
+    pub allow_tools: Vec<String>,
+
+    /// Turn off an optional capability and its methods (repeatable)
+    #[arg(long = "disable-capability", value_enum, value_name = "CAPABILITY")]
+    pub disabled_capabilities: Vec<Capability>,
+
+    /// Message framing on stdio: newline-delimited JSON, or LSP-style
+    /// `Content-Length` headers
+    #[arg(long, value_enum, default_value_t = Framing::Lines)]
+    pub framing: Framing,
+
+    /// Treat backslashes in `table_name` as path separators, so names from
+    /// Windows-authored configs (e.g. `sub\\customers`) resolve on any host
+    #[arg(long, default_value_t = false)]
+    pub accept_windows_paths: bool,
+
+    /// Open the first table in `location` and read a record from it at
+    /// startup, logging the outcome
+    #[arg(long, default_value_t = false)]
+    pub startup_selftest: bool,
+
+    /// Refuse to start when the startup self-test fails
+    #[arg(long, default_value_t = false, requires = "startup_selftest")]
+    pub selftest_fatal: bool,
+}
+
+/// The options a `--config` file may set, under the same names as their
+/// flags (with underscores).
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    location: Option<Locations>,
+    port: Option<u16>,
+    permit_editing: Option<bool>,
+    encoding: Option<String>,
+    default_limit: Option<i32>,
+    max_limit: Option<i32>,
+}
+
+/// `location` in a config file: one directory or a list of them.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Locations {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Args {
+    /// Parses the command line, then fills the options it leaves unset from
+    /// the `--config` file, if there is one. Exits with a usage error when
+    /// the file can't be read or no location is given either way.
+    pub fn load() -> Args {
+        let matches = Args::command().get_matches();
+        let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        if let Some(path) = &args.config {
+            let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+            let file = read_config(path).unwrap_or_else(|e| config_error(ErrorKind::Io, e));
+            match (file.location, from_cli("location")) {
+                (Some(Locations::One(location)), false) => args.location = vec![location],
+                (Some(Locations::Many(locations)), false) => args.location = locations,
+                _ => {}
+            }
+            if let (Some(port), false) = (file.port, from_cli("port")) {
+                args.port = Some(port);
+            }
+            if let (Some(permit), false) = (file.permit_editing, from_cli("permit_editing")) {
+                args.permit_editing = permit;
+            }
+            if let (Some(encoding), false) = (file.encoding, from_cli("encoding")) {
+                args.encoding = encoding.parse().unwrap_or_else(|e| {
+                    config_error(
+                        ErrorKind::InvalidValue,
+                        format!("{}: encoding: {}", path.display(), e),
+                    )
+                });
+            }
+            if let (Some(limit), false) = (file.default_limit, from_cli("default_limit")) {
+                args.default_limit = limit;
+            }
+            if let (Some(limit), false) = (file.max_limit, from_cli("max_limit")) {
+                args.max_limit = limit;
+            }
+        }
+        if args.default_limit < 1 || args.max_limit < 1 {
+            config_error(
+                ErrorKind::InvalidValue,
+                "default_limit and max_limit must be at least 1".to_string(),
+            );
+        }
+        if args.default_limit > args.max_limit {
+            config_error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--default-limit ({}) exceeds --max-limit ({})",
+                    args.default_limit, args.max_limit
+                ),
+            );
+        }
+        args.location = args
+            .location
+            .iter()
+            .flat_map(std::env::split_paths)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .collect();
+        if args.location.is_empty() {
+            config_error(
+                ErrorKind::MissingRequiredArgument,
+                "a location is required: pass --location or set `location` in the --config file"
+                    .to_string(),
+            );
+        }
+        for dir in &args.location {
+            if let Err(e) = check_location(Path::new(dir), args.permit_editing) {
+                config_error(ErrorKind::Io, format!("location {}: {}", dir, e));
+            }
+        }
+        args
+    }
+
+    /// The directory new tables and relative file paths are placed in.
+    pub fn primary_location(&self) -> &Path {
+        Path::new(&self.location[0])
+    }
+
+    /// Whether `--allow-tool`/`--deny-tool` expose `tool` to clients.
+    pub fn tool_allowed(&self, tool: &str) -> bool {
+        (self.allow_tools.is_empty() || self.allow_tools.iter().any(|t| t == tool))
+            && !self.deny_tools.iter().any(|t| t == tool)
+    }
+
+    pub fn capability_enabled(&self, capability: Capability) -> bool {
+        !self.disabled_capabilities.contains(&capability)
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// One JSON message per line
+    Lines,
+    /// `Content-Length: N` headers, a blank line, then N bytes of JSON
+    #[value(alias = "content-length")]
+    Lsp,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateFormat {
+    /// Formatted strings, e.g. "2024-03-01" or "14:30:00.000"
+    Iso,
+    /// The stored Paradox value
+    Raw,
+    /// Unix seconds for dates and timestamps, seconds since midnight for
+    /// times
+    Seconds,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// `resources/*` methods and result links
+    Resources,
+    /// `logging/setLevel`
+    Logging,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadonlyFieldMode {
+    /// Fail the write and report the blocked fields
+    Reject,
+    /// Drop the blocked fields and write the rest
+    Ignore,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldOrder {
+    /// Keys sorted alphabetically
+    Alpha,
+    /// Keys in the order the fields are declared in the table
+    Schema,
+    /// The listed fields first, the rest in schema order
+    Custom(Vec<String>),
+}
+
+impl std::str::FromStr for FieldOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alpha" => Ok(FieldOrder::Alpha),
+            "schema" => Ok(FieldOrder::Schema),
+            _ => {
+                let names: Vec<String> = s
+                    .split(',')
+                    .map(|n| n.trim().to_string())
+                    .filter(|n| !n.is_empty())
+                    .collect();
+                if names.is_empty() {
+                    Err("expected `alpha`, `schema` or a list of field names".to_string())
+                } else {
+                    Ok(FieldOrder::Custom(names))
+                }
+            }
+        }
+    }
+}
+
+fn read_config(path: &Path) -> Result<ConfigFile, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("can't read config file {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("invalid config file {}: {}", path.display(), e))
+}
+
+/// Confirms `dir` is a directory that can be listed and, when editing is
+/// permitted, written to, so a misconfigured location stops startup
+/// instead of failing every tool call.
+fn check_location(dir: &Path, writable: bool) -> Result<(), String> {
+    let metadata = std::fs::metadata(dir).map_err(|e| e.to_string())?;
+    if !metadata.is_dir() {
+        return Err("not a directory".to_string());
+    }
+    std::fs::read_dir(dir).map_err(|e| format!("not readable: {}", e))?;
+    if writable {
+        let probe = dir.join(format!(".paradox-mcp-{}.tmp", std::process::id()));
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .map_err(|e| format!("not writable, but --permit-editing is set: {}", e))?;
+        let _ = std::fs::remove_file(&probe);
+    }
+    Ok(())
+}
+
+fn config_error(kind: ErrorKind, message: String) -> ! {
+    Args::command().error(kind, message).exit()
 }
@@ -0,0 +1,39 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard, padded base64 (RFC 4648).
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// The number of bytes a padded base64 string decodes to.
+pub fn decoded_len(encoded: &str) -> usize {
+    let padding = encoded.bytes().rev().take_while(|&b| b == b'=').count();
+    (encoded.len() / 4 * 3).saturating_sub(padding)
+}
+
+/// Cuts an encoding of more than `max_bytes` bytes down to the encoding of
+/// its first bytes, keeping whole 3-byte groups so the result still decodes
+/// cleanly (to at most `max_bytes` bytes). Returns whether it cut anything.
+pub fn truncate(encoded: &mut String, max_bytes: usize) -> bool {
+    if decoded_len(encoded) <= max_bytes {
+        return false;
+    }
+    encoded.truncate(max_bytes / 3 * 4);
+    true
+}
@@ -0,0 +1,63 @@
+use serde_json::{json, Value};
+
+/// Paradox BCD fields hold at most 32 digits, decimals included.
+const MAX_DIGITS: usize = 32;
+
+/// Decimals carry exactly through an f64 up to this many significant digits.
+const MAX_EXACT_DIGITS: usize = 15;
+
+/// Turns the decimal string pxlib decodes a BCD field into a JSON number
+/// (an integer when there are no decimals) when it fits exactly, and keeps
+/// it as a string otherwise.
+pub fn to_json(decoded: &str) -> Value {
+    let decoded = decoded.trim();
+    if let Ok(v) = decoded.parse::<i64>() {
+        return json!(v);
+    }
+    let significant = decoded
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .skip_while(|&b| b == b'0')
+        .count();
+    match decoded.parse::<f64>() {
+        Ok(v) if v.is_finite() && significant <= MAX_EXACT_DIGITS => json!(v),
+        _ => Value::String(decoded.to_string()),
+    }
+}
+
+/// Reads a value to be written to a BCD field with `decimals` decimal
+/// places: a number, or a decimal string for values an f64 can't hold.
+/// Returns the string `PX_put_data_bcd` expects.
+pub fn from_json(value: &Value, decimals: usize) -> Result<String, String> {
+    let text = match value {
+        Value::Number(n) => {
+            let v = n
+                .as_f64()
+                .ok_or_else(|| format!("{} is not a decimal", n))?;
+            format!("{:.*}", decimals, v)
+        }
+        Value::String(s) => s.trim().to_string(),
+        _ => return Err(format!("{} is not a decimal", value)),
+    };
+
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(&text);
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if int_part.is_empty() || !all_digits(int_part) || !all_digits(frac_part) {
+        return Err(format!("'{}' is not a decimal", text));
+    }
+    if frac_part.len() > decimals {
+        return Err(format!(
+            "'{}' has more than {} decimal places",
+            text, decimals
+        ));
+    }
+    if int_part.trim_start_matches('0').len() + decimals > MAX_DIGITS {
+        return Err(format!(
+            "'{}' has more than {} digits before the decimal point",
+            text,
+            MAX_DIGITS - decimals
+        ));
+    }
+    Ok(text)
+}
@@ -0,0 +1,201 @@
+use crate::pxlib;
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+/// How ALPHA bytes are turned into text, from `--encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Bytes are taken as UTF-8, invalid sequences replaced.
+    Utf8,
+    /// Each table's stored codepage picks the charset.
+    Auto,
+    Fixed(Charset),
+}
+
+impl std::str::FromStr for TextEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => Ok(TextEncoding::Utf8),
+            "auto" => Ok(TextEncoding::Auto),
+            name => Charset::from_name(name).map(TextEncoding::Fixed).ok_or_else(|| {
+                format!(
+                    "unknown encoding '{}'; expected `auto`, `utf8`, a codepage such as `cp1252` or `cp850`, or an encoding label such as `iso-8859-2`",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+/// A legacy single- or multi-byte charset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// A DOS codepage encoding_rs doesn't cover: the characters of bytes
+    /// 0x80..=0xFF, below which it matches ASCII.
+    Dos(&'static str, &'static [char; 128]),
+    Whatwg(&'static Encoding),
+}
+
+impl Charset {
+    /// `cpNNN` codepage names, plus any WHATWG encoding label.
+    fn from_name(name: &str) -> Option<Self> {
+        if let Some(codepage) = name.strip_prefix("cp").and_then(|n| n.parse().ok()) {
+            return Charset::from_codepage(codepage);
+        }
+        let encoding = Encoding::for_label(name.as_bytes())?;
+        // UTF-16 can't be written byte for byte into a C string.
+        (encoding.output_encoding() == encoding).then_some(Charset::Whatwg(encoding))
+    }
+
+    /// The charset of a Paradox codepage number, where one is known.
+    fn from_codepage(codepage: u32) -> Option<Self> {
+        match codepage {
+            437 => Some(Charset::Dos("cp437", &CP437)),
+            850 => Some(Charset::Dos("cp850", &CP850)),
+            866 => Some(Charset::Whatwg(encoding_rs::IBM866)),
+            874 => Some(Charset::Whatwg(encoding_rs::WINDOWS_874)),
+            1250..=1258 => {
+                Encoding::for_label(format!("windows-{}", codepage).as_bytes()).map(Charset::Whatwg)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Charset::Dos(name, _) => name,
+            Charset::Whatwg(encoding) => encoding.name(),
+        }
+    }
+
+    fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        match self {
+            Charset::Dos(_, high) => {
+                if bytes.is_ascii() {
+                    return String::from_utf8_lossy(bytes);
+                }
+                Cow::Owned(
+                    bytes
+                        .iter()
+                        .map(|&b| {
+                            if b < 0x80 {
+                                b as char
+                            } else {
+                                high[b as usize - 0x80]
+                            }
+                        })
+                        .collect(),
+                )
+            }
+            Charset::Whatwg(encoding) => encoding.decode_without_bom_handling(bytes).0,
+        }
+    }
+
+    /// `None` when `text` has a character the charset can't represent.
+    fn encode<'a>(&self, text: &'a str) -> Option<Cow<'a, [u8]>> {
+        match self {
+            Charset::Dos(_, high) => {
+                if text.is_ascii() {
+                    return Some(Cow::Borrowed(text.as_bytes()));
+                }
+                text.chars()
+                    .map(|c| {
+                        if c.is_ascii() {
+                            Some(c as u8)
+                        } else {
+                            high.iter().position(|&h| h == c).map(|i| (i + 0x80) as u8)
+                        }
+                    })
+                    .collect::<Option<Vec<u8>>>()
+                    .map(Cow::Owned)
+            }
+            Charset::Whatwg(encoding) => {
+                let (bytes, _, unmappable) = encoding.encode(text);
+                (!unmappable).then_some(bytes)
+            }
+        }
+    }
+}
+
+static ENCODING: OnceLock<TextEncoding> = OnceLock::new();
+
+/// Sets the `--encoding` used for ALPHA fields. Called once at startup.
+pub fn init(encoding: TextEncoding) {
+    let _ = ENCODING.set(encoding);
+}
+
+/// The charset ALPHA fields of `pxdoc` are stored in; `None` for UTF-8.
+unsafe fn charset_for(pxdoc: *mut pxlib::pxdoc_t) -> Option<Charset> {
+    match ENCODING.get().copied().unwrap_or(TextEncoding::Utf8) {
+        TextEncoding::Utf8 => None,
+        TextEncoding::Fixed(charset) => Some(charset),
+        TextEncoding::Auto => {
+            let mut codepage: std::os::raw::c_float = 0.0;
+            if pxlib::PX_get_value(pxdoc, c"codepage".as_ptr(), &mut codepage) != 0 {
+                return None;
+            }
+            Charset::from_codepage(codepage as u32)
+        }
+    }
+}
+
+/// Decodes the bytes of an ALPHA field of `pxdoc`.
+pub unsafe fn decode(pxdoc: *mut pxlib::pxdoc_t, bytes: &[u8]) -> String {
+    match charset_for(pxdoc) {
+        Some(charset) => charset.decode(bytes).into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Encodes text for an ALPHA field of `pxdoc`, or names the charset that
+/// can't represent it.
+pub unsafe fn encode<'a>(
+    pxdoc: *mut pxlib::pxdoc_t,
+    text: &'a str,
+) -> Result<Cow<'a, [u8]>, &'static str> {
+    match charset_for(pxdoc) {
+        Some(charset) => charset.encode(text).ok_or(charset.name()),
+        None => Ok(Cow::Borrowed(text.as_bytes())),
+    }
+}
+
+static CP437: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00A2}', '\u{00A3}', '\u{00A5}', '\u{20A7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{2310}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{03B1}', '\u{00DF}', '\u{0393}', '\u{03C0}', '\u{03A3}', '\u{03C3}', '\u{00B5}', '\u{03C4}',
+    '\u{03A6}', '\u{0398}', '\u{03A9}', '\u{03B4}', '\u{221E}', '\u{03C6}', '\u{03B5}', '\u{2229}',
+    '\u{2261}', '\u{00B1}', '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00F7}', '\u{2248}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{207F}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
+
+static CP850: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00F8}', '\u{00A3}', '\u{00D8}', '\u{00D7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{00AE}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{00C1}', '\u{00C2}', '\u{00C0}',
+    '\u{00A9}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{00A2}', '\u{00A5}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{00E3}', '\u{00C3}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{00A4}',
+    '\u{00F0}', '\u{00D0}', '\u{00CA}', '\u{00CB}', '\u{00C8}', '\u{0131}', '\u{00CD}', '\u{00CE}',
+    '\u{00CF}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{00A6}', '\u{00CC}', '\u{2580}',
+    '\u{00D3}', '\u{00DF}', '\u{00D4}', '\u{00D2}', '\u{00F5}', '\u{00D5}', '\u{00B5}', '\u{00FE}',
+    '\u{00DE}', '\u{00DA}', '\u{00DB}', '\u{00D9}', '\u{00FD}', '\u{00DD}', '\u{00AF}', '\u{00B4}',
+    '\u{00AD}', '\u{00B1}', '\u{2017}', '\u{00BE}', '\u{00B6}', '\u{00A7}', '\u{00F7}', '\u{00B8}',
+    '\u{00B0}', '\u{00A8}', '\u{00B7}', '\u{00B9}', '\u{00B3}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
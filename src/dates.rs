@@ -0,0 +1,248 @@
+use crate::args::DateFormat;
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Converts a count of days since 1970-01-01 into a proleptic Gregorian
+/// `(year, month, day)` (Howard Hinnant's `civil_from_days`).
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The inverse of `civil_from_days`: days since 1970-01-01 for a proleptic
+/// Gregorian date.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let m = i64::from(month);
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// Paradox day number of 1970-01-01; Paradox counts 0001-01-01 as day 1.
+const PARADOX_UNIX_EPOCH: i64 = 719_163;
+
+/// The day after 9999-12-31, the last date Paradox can hold.
+const MAX_PARADOX_DAY: i64 = 3_652_060;
+
+/// Formats a Paradox DATE (day number) as `YYYY-MM-DD`.
+pub fn format_paradox_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days - PARADOX_UNIX_EPOCH);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Parses `YYYY-MM-DD` into a Paradox day number, rejecting dates that
+/// don't exist (e.g. February 30th).
+pub fn parse_paradox_date(s: &str) -> Option<i64> {
+    let mut parts = s.trim().splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let (year, month, day): (i64, u32, u32) =
+        (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    let days = days_from_civil(year, month, day);
+    (year >= 1 && civil_from_days(days) == (year, month, day)).then_some(days + PARADOX_UNIX_EPOCH)
+}
+
+/// Formats a Paradox TIMESTAMP (milliseconds since the start of Paradox
+/// day 0) as RFC 3339 UTC, e.g. `2023-04-01T13:45:00Z`, with `.mmm` only
+/// when there is a sub-second part. `None` for values outside years
+/// 1..=9999.
+pub fn format_paradox_timestamp(ms: f64) -> Option<String> {
+    if !ms.is_finite() || ms < MS_PER_DAY as f64 || ms >= (MAX_PARADOX_DAY * MS_PER_DAY) as f64 {
+        return None;
+    }
+    let ms = ms.round() as i64;
+    let time = format_time_of_day(ms % MS_PER_DAY);
+    let time = time.strip_suffix(".000").unwrap_or(&time);
+    Some(format!(
+        "{}T{}Z",
+        format_paradox_date(ms / MS_PER_DAY),
+        time
+    ))
+}
+
+/// Parses `YYYY-MM-DDTHH:MM[:SS[.mmm]]` (optionally with a space instead
+/// of `T`, and a trailing `Z` or RFC 3339 `±HH:MM` offset) into a Paradox
+/// TIMESTAMP, in UTC.
+pub fn parse_paradox_timestamp(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let s = s.strip_suffix(['Z', 'z']).unwrap_or(s);
+    let (date, time) = s.split_once(['T', 't', ' '])?;
+    let (time, offset_ms) = match time.rfind(['+', '-']) {
+        Some(i) => {
+            let offset = parse_time_of_day(&time[i + 1..])?;
+            (
+                &time[..i],
+                if &time[i..=i] == "-" { -offset } else { offset },
+            )
+        }
+        None => (time, 0),
+    };
+    let days = parse_paradox_date(date)?;
+    Some((days * MS_PER_DAY + parse_time_of_day(time)? - offset_ms) as f64)
+}
+
+/// Formats a filesystem timestamp as an ISO-8601 / RFC 3339 UTC string.
+pub fn format_system_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rem = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Formats a Paradox TIME (milliseconds since midnight) as `HH:MM:SS.mmm`.
+pub fn format_time_of_day(ms: i64) -> String {
+    let secs = ms / 1000;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        ms % 1000
+    )
+}
+
+/// Parses `HH:MM`, `HH:MM:SS` or `HH:MM:SS.mmm` into milliseconds since
+/// midnight.
+pub fn parse_time_of_day(s: &str) -> Option<i64> {
+    let (hms, frac) = match s.trim().split_once('.') {
+        Some((hms, frac)) => (hms, Some(frac)),
+        None => (s.trim(), None),
+    };
+    let parts: Vec<i64> = hms
+        .split(':')
+        .map(|p| p.parse().ok().filter(|_| (1..=2).contains(&p.len())))
+        .collect::<Option<_>>()?;
+    let (h, m, sec) = match parts[..] {
+        [h, m] => (h, m, 0),
+        [h, m, sec] => (h, m, sec),
+        _ => return None,
+    };
+    if h > 23 || m > 59 || sec > 59 {
+        return None;
+    }
+    let millis = match frac {
+        Some(f) if !f.is_empty() && f.len() <= 3 && f.bytes().all(|b| b.is_ascii_digit()) => {
+            f.parse::<i64>().ok()? * 10_i64.pow(3 - f.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+    Some(((h * 60 + m) * 60 + sec) * 1000 + millis)
+}
+
+/// Renders a decoded `HH:MM:SS.mmm` TIME string in the `--date-format`
+/// representation; other values pass through unchanged.
+pub fn render_time(value: &Value, format: DateFormat) -> Value {
+    let Some(ms) = value.as_str().and_then(parse_time_of_day) else {
+        return value.clone();
+    };
+    match format {
+        DateFormat::Iso => value.clone(),
+        DateFormat::Raw => json!(ms),
+        DateFormat::Seconds if ms % 1000 == 0 => json!(ms / 1000),
+        DateFormat::Seconds => json!(ms as f64 / 1000.0),
+    }
+}
+
+/// Renders a decoded `YYYY-MM-DD` DATE string in the `--date-format`
+/// representation: the Paradox day number for `raw`, Unix seconds at UTC
+/// midnight for `seconds`.
+pub fn render_date(value: &Value, format: DateFormat) -> Value {
+    let Some(days) = value.as_str().and_then(parse_paradox_date) else {
+        return value.clone();
+    };
+    match format {
+        DateFormat::Iso => value.clone(),
+        DateFormat::Raw => json!(days),
+        DateFormat::Seconds => json!((days - PARADOX_UNIX_EPOCH) * 86_400),
+    }
+}
+
+/// Renders a decoded TIMESTAMP string in the `--date-format`
+/// representation: the stored Paradox milliseconds for `raw`, Unix seconds
+/// for `seconds`.
+pub fn render_timestamp(value: &Value, format: DateFormat) -> Value {
+    let Some(ms) = value.as_str().and_then(parse_paradox_timestamp) else {
+        return value.clone();
+    };
+    let unix_ms = ms as i64 - PARADOX_UNIX_EPOCH * MS_PER_DAY;
+    match format {
+        DateFormat::Iso => value.clone(),
+        DateFormat::Raw => json!(ms),
+        DateFormat::Seconds if unix_ms % 1000 == 0 => json!(unix_ms / 1000),
+        DateFormat::Seconds => json!(unix_ms as f64 / 1000.0),
+    }
+}
+
+/// Reads a TIME value to be written: a time string in any format, or a
+/// number in the `--date-format` unit (milliseconds unless `seconds`).
+pub fn time_input_ms(value: &Value, format: DateFormat) -> Option<i64> {
+    let ms = match value {
+        Value::String(s) => parse_time_of_day(s)?,
+        Value::Number(n) if format == DateFormat::Seconds => (n.as_f64()? * 1000.0).round() as i64,
+        Value::Number(n) => n.as_i64()?,
+        _ => return None,
+    };
+    (0..MS_PER_DAY).contains(&ms).then_some(ms)
+}
+
+/// Reads a DATE value to be written: a `YYYY-MM-DD` string, or a number in
+/// the `--date-format` unit (a Paradox day number unless `seconds`, where
+/// it must fall on a UTC midnight).
+pub fn date_input_days(value: &Value, format: DateFormat) -> Option<i64> {
+    let days = match value {
+        Value::String(s) => parse_paradox_date(s)?,
+        Value::Number(n) if format == DateFormat::Seconds => {
+            let secs = n.as_i64()?;
+            if secs % 86_400 != 0 {
+                return None;
+            }
+            secs / 86_400 + PARADOX_UNIX_EPOCH
+        }
+        Value::Number(n) => n.as_i64()?,
+        _ => return None,
+    };
+    (1..MAX_PARADOX_DAY).contains(&days).then_some(days)
+}
+
+/// Reads a TIMESTAMP value to be written: an RFC 3339 string, or a number
+/// in the `--date-format` unit (Paradox milliseconds unless `seconds`,
+/// where it is Unix seconds).
+pub fn timestamp_input_ms(value: &Value, format: DateFormat) -> Option<f64> {
+    let ms = match value {
+        Value::String(s) => parse_paradox_timestamp(s)?,
+        Value::Number(n) if format == DateFormat::Seconds => {
+            n.as_f64()? * 1000.0 + (PARADOX_UNIX_EPOCH * MS_PER_DAY) as f64
+        }
+        Value::Number(n) => n.as_f64()?,
+        _ => return None,
+    };
+    let range = MS_PER_DAY as f64..(MAX_PARADOX_DAY * MS_PER_DAY) as f64;
+    range.contains(&ms).then_some(ms)
+}
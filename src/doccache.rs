@@ -0,0 +1,122 @@
+use crate::args::Args;
+use crate::errors::ToolError;
+use crate::metrics;
+use crate::pxdoc::PxDoc;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// An idle open handle and what it was opened against.
+struct Entry {
+    path: PathBuf,
+    doc: PxDoc,
+    opened: Instant,
+    stamp: Option<FileStamp>,
+}
+
+/// Modification time and size of a table file, to spot changes made
+/// behind the cache's back.
+type FileStamp = (SystemTime, u64);
+
+/// Idle handles, least recently used first.
+static CACHE: LazyLock<Mutex<Vec<Entry>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// A table handle for reading. With `--handle-cache-size` set it comes from
+/// the cache when a fresh one is idle there, and goes back on drop instead
+/// of being closed.
+pub struct ReadDoc {
+    doc: Option<PxDoc>,
+    path: PathBuf,
+    opened: Instant,
+    stamp: Option<FileStamp>,
+    capacity: usize,
+}
+
+impl Deref for ReadDoc {
+    type Target = PxDoc;
+
+    fn deref(&self) -> &PxDoc {
+        self.doc.as_ref().expect("handle present until drop")
+    }
+}
+
+impl Drop for ReadDoc {
+    fn drop(&mut self) {
+        let Some(doc) = self.doc.take() else { return };
+        if self.capacity == 0 {
+            return;
+        }
+        let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        cache.push(Entry {
+            path: std::mem::take(&mut self.path),
+            doc,
+            opened: self.opened,
+            stamp: self.stamp,
+        });
+        // Evicted handles close as they drop.
+        let excess = cache.len().saturating_sub(self.capacity);
+        cache.drain(..excess);
+    }
+}
+
+/// Opens the table at `path` for reading, with its blob file attached,
+/// reusing an idle cached handle unless it has outlived
+/// `--handle-cache-ttl-secs` or the file changed since it was opened.
+pub fn open(path: &Path, args: &Args) -> Result<ReadDoc, ToolError> {
+    open_checked(path, args, args.strict_types)
+}
+
+/// Like `open`, but a record size mismatch stays a warning even under
+/// `--strict-types`, for callers that bring their own field offsets.
+pub fn open_relaxed(path: &Path, args: &Args) -> Result<ReadDoc, ToolError> {
+    open_checked(path, args, false)
+}
+
+fn open_checked(path: &Path, args: &Args, strict: bool) -> Result<ReadDoc, ToolError> {
+    let capacity = args.handle_cache_size;
+    let stamp = file_stamp(path);
+    if capacity > 0 {
+        let ttl = Duration::from_secs(args.handle_cache_ttl_secs);
+        let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        cache.retain(|e| e.opened.elapsed() < ttl);
+        if let Some(pos) = cache.iter().rposition(|e| e.path == path) {
+            let entry = cache.remove(pos);
+            if entry.stamp.is_some() && entry.stamp == stamp {
+                metrics::record_handle_cache(true);
+                entry.doc.check_layout(strict)?;
+                return Ok(ReadDoc {
+                    doc: Some(entry.doc),
+                    path: entry.path,
+                    opened: entry.opened,
+                    stamp: entry.stamp,
+                    capacity,
+                });
+            }
+        }
+        metrics::record_handle_cache(false);
+    }
+
+    let mut doc = PxDoc::new()?;
+    doc.open(path, strict)?;
+    doc.attach_blob_file(path);
+    Ok(ReadDoc {
+        doc: Some(doc),
+        path: path.to_path_buf(),
+        opened: Instant::now(),
+        stamp,
+        capacity,
+    })
+}
+
+/// Closes any idle handle on the table at `path`. Writers call this so no
+/// later read sees data from before their change.
+pub fn invalidate(path: &Path) {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|e| e.path != path);
+}
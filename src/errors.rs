@@ -0,0 +1,86 @@
+use crate::pxerror;
+use serde_json::{json, Value};
+use std::os::raw::c_int;
+
+/// Structured tool failure: a stable machine-readable `code` plus a human
+/// message. Rendered as an MCP `isError` result, with the code in `_meta`.
+#[derive(Debug)]
+pub struct ToolError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ToolError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        ToolError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn into_value(self) -> Value {
+        json!({
+            "isError": true,
+            "content": [{ "type": "text", "text": self.message }],
+            "_meta": { "errorCode": self.code }
+        })
+    }
+}
+
+/// The pxlib operation a return code came from.
+#[derive(Debug, Clone, Copy)]
+pub enum PxOp {
+    Open,
+    Write,
+    Create,
+}
+
+impl PxOp {
+    fn code(self) -> &'static str {
+        match self {
+            PxOp::Open => "PX_OPEN_FAILED",
+            PxOp::Write => "PX_WRITE_FAILED",
+            PxOp::Create => "PX_CREATE_FAILED",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            PxOp::Open => "open",
+            PxOp::Write => "write to",
+            PxOp::Create => "create",
+        }
+    }
+}
+
+/// Maps a pxlib return code (negative on failure) into a `ToolError`,
+/// including pxlib's own diagnostic when one was captured.
+pub fn px_result(code: c_int, op: PxOp, table: &str) -> Result<(), ToolError> {
+    if code >= 0 {
+        return Ok(());
+    }
+    let message = match pxerror::take_error() {
+        Some(detail) => format!(
+            "Failed to {} table '{}': {} (pxlib returned {}).",
+            op.verb(),
+            table,
+            detail,
+            code
+        ),
+        None => format!(
+            "Failed to {} table '{}' (pxlib returned {}).",
+            op.verb(),
+            table,
+            code
+        ),
+    };
+    Err(ToolError::new(op.code(), message))
+}
+
+pub fn px_init_error() -> ToolError {
+    ToolError::new("PX_INIT_FAILED", "Failed to initialize PX library.")
+}
+
+pub fn invalid_path_error() -> ToolError {
+    ToolError::new("INVALID_PATH", "Invalid table path string.")
+}
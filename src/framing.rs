@@ -0,0 +1,122 @@
+use crate::args::Framing;
+use std::io::{self, BufRead, Read, Write};
+
+/// Largest header-framed body accepted, matching the SSE transport's cap.
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/// One message read from the client.
+pub struct Incoming {
+    pub body: String,
+    /// The input ended before the message did: a newline-delimited line
+    /// without its newline, or a header-framed body shorter than announced.
+    pub truncated: bool,
+    /// Why the message was refused unread, e.g. a line that isn't UTF-8 or
+    /// a header-framed body over `MAX_BODY`; `body` is then empty.
+    pub rejected: Option<&'static str>,
+}
+
+/// Reads the next message, or `None` at a clean end of input.
+pub fn read_message(reader: &mut impl BufRead, framing: Framing) -> io::Result<Option<Incoming>> {
+    match framing {
+        Framing::Lines => read_line_message(reader),
+        Framing::Lsp => read_framed_message(reader),
+    }
+}
+
+/// Writes one message in the given framing and flushes it.
+pub fn write_message(writer: &mut impl Write, framing: Framing, body: &str) -> io::Result<()> {
+    match framing {
+        Framing::Lines => writeln!(writer, "{}", body)?,
+        Framing::Lsp => write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?,
+    }
+    writer.flush()
+}
+
+/// Reads the next non-blank line. A line that isn't valid UTF-8 is
+/// refused on its own rather than ending the session.
+fn read_line_message(reader: &mut impl BufRead) -> io::Result<Option<Incoming>> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        let truncated = line.last() != Some(&b'\n');
+        let Ok(line) = std::str::from_utf8(&line) else {
+            return Ok(Some(Incoming {
+                body: String::new(),
+                truncated,
+                rejected: Some("Parse error: message is not valid UTF-8"),
+            }));
+        };
+        let body = line.trim_end_matches(['\r', '\n']);
+        if !body.trim().is_empty() {
+            return Ok(Some(Incoming {
+                body: body.to_string(),
+                truncated,
+                rejected: None,
+            }));
+        }
+    }
+}
+
+/// Reads `Content-Length: N` headers up to the blank line, then exactly
+/// N bytes of body. Other headers (e.g. `Content-Type`) are ignored, and a
+/// header block without a usable length is refused on its own.
+fn read_framed_message(reader: &mut impl BufRead) -> io::Result<Option<Incoming>> {
+    let mut content_length = None;
+    let mut header = Vec::new();
+    let mut saw_header = false;
+    loop {
+        header.clear();
+        if reader.read_until(b'\n', &mut header)? == 0 {
+            if saw_header {
+                return Ok(Some(Incoming {
+                    body: String::new(),
+                    truncated: true,
+                    rejected: None,
+                }));
+            }
+            return Ok(None);
+        }
+        let header = String::from_utf8_lossy(&header);
+        let line = header.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            if saw_header {
+                break;
+            }
+            continue;
+        }
+        saw_header = true;
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = content_length else {
+        return Ok(Some(Incoming {
+            body: String::new(),
+            truncated: false,
+            rejected: Some("Parse error: message headers without a valid Content-Length"),
+        }));
+    };
+    // The length is the client's word, so nothing is preallocated from it;
+    // an oversized body is read past to keep the stream in step.
+    if length > MAX_BODY {
+        let skipped = io::copy(&mut reader.take(length as u64), &mut io::sink())?;
+        return Ok(Some(Incoming {
+            body: String::new(),
+            truncated: (skipped as usize) < length,
+            rejected: Some("Parse error: message body exceeds the size limit"),
+        }));
+    }
+    let mut body = Vec::new();
+    reader.take(length as u64).read_to_end(&mut body)?;
+    Ok(Some(Incoming {
+        truncated: body.len() < length,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        rejected: None,
+    }))
+}
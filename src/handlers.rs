@@ -1,780 +1,5719 @@
-use crate::args::Args;
-use crate::mcp::RpcRequest;
+use crate::args::{Args, Capability, DateFormat, FieldOrder, ReadonlyFieldMode};
+use crate::base64;
+use crate::bcd;
+use crate::charset;
+use crate::dates;
+use crate::doccache;
+use crate::errors::{invalid_path_error, px_init_error, px_result, PxOp, ToolError};
+use crate::locks;
+use crate::logging;
+use crate::mcp::{self, RpcError, RpcRequest};
+use crate::metrics;
+use crate::pxdoc::PxDoc;
+use crate::pxerror;
 use crate::pxlib;
+use crate::resources::{self, TableResource};
+use crate::session::ServerState;
+use rayon::prelude::*;
+use regex::Regex;
 use serde_json::{json, Map, Value};
+use std::collections::HashMap;
 use std::ffi::CString;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
 
-pub fn handle_request(req: &RpcRequest, args: &Args) -> Value {
-    match req.method.as_str() {
+pub fn handle_request(req: &RpcRequest, state: &ServerState) -> Result<Value, RpcError> {
+    let args = &state.args;
+    let capability = match req.method.split('/').next() {
+        Some("resources") => Some(Capability::Resources),
+        Some("logging") => Some(Capability::Logging),
+        _ => None,
+    };
+    if capability.is_some_and(|c| !args.capability_enabled(c)) {
+        return Err(RpcError::method_not_found(&req.method));
+    }
+
+    let result = match req.method.as_str() {
         "initialize" => {
+            let client = state.session.initialize(req.params.as_ref());
+            let protocol_version =
+                mcp::negotiate_protocol_version(client.protocol_version.as_deref());
+            log::info!(
+                "Client connected: {} (protocol {}, using {})",
+                client.label(),
+                client.protocol_version.as_deref().unwrap_or("unspecified"),
+                protocol_version
+            );
+            let mut capabilities = json!({ "tools": {} });
+            if args.capability_enabled(Capability::Logging) {
+                capabilities["logging"] = json!({});
+            }
+            if args.capability_enabled(Capability::Resources) {
+                capabilities["resources"] = json!({});
+            }
             json!({
-                "protocolVersion": "2024-11-05",
-                "capabilities": {
-                    "tools": {}
-                },
+                "protocolVersion": protocol_version,
+                "capabilities": capabilities,
                 "serverInfo": {
                     "name": "paradox-mcp-rust",
-                    "version": "1.0.0"
+                    "version": env!("CARGO_PKG_VERSION")
                 }
             })
         }
-        "tools/list" => {
+        "ping" => json!({}),
+        "logging/setLevel" => {
+            let level = req
+                .params
+                .as_ref()
+                .and_then(|p| p.get("level"))
+                .and_then(|l| l.as_str())
+                .unwrap_or("");
+            match logging::level_from_mcp(level) {
+                Some(_) if args.quiet => {}
+                Some(filter) => logging::set_level(filter),
+                None => {
+                    return Err(RpcError::invalid_params(format!(
+                        "Unknown log level: {:?}",
+                        level
+                    )))
+                }
+            }
+            json!({})
+        }
+        "resources/read" => {
+            let uri = req
+                .params
+                .as_ref()
+                .and_then(|p| p.get("uri"))
+                .and_then(|u| u.as_str())
+                .ok_or_else(|| RpcError::invalid_params("Missing uri"))?;
+            let result = if uri.starts_with(resources::RESULT_URI_PREFIX) {
+                let (tool, arguments) = resources::resolve_link(uri, state)?;
+                call_tool(&tool, &arguments, state)?
+            } else {
+                if !args.tool_allowed("read_table_data") {
+                    return Err(RpcError::resource_not_found(uri));
+                }
+                let opts = ReadOptions::from_arguments(&Map::new(), args);
+                match resources::parse_table_uri(uri) {
+                    Some(TableResource::Table(name)) => table_preview(&name, args, &opts),
+                    Some(TableResource::Record(name, index)) => {
+                        handle_get_record(&name, args, index, &opts)
+                    }
+                    None => return Err(RpcError::resource_not_found(uri)),
+                }
+            };
+            if result.get("isError").and_then(|e| e.as_bool()) == Some(true) {
+                return Err(RpcError::internal(format!(
+                    "Failed to read {}: {}",
+                    uri,
+                    result["content"][0]["text"]
+                        .as_str()
+                        .unwrap_or("unknown error")
+                )));
+            }
+            let text = result
+                .get("content")
+                .and_then(|c| c.as_array())
+                .and_then(|c| c.last())
+                .and_then(|block| block.get("text"))
+                .cloned()
+                .unwrap_or(Value::Null);
             json!({
-                "tools": [
-                    {
-                        "name": "get_server_status",
-                        "description": "Get the status and configuration of the Paradox MCP server",
-                        "inputSchema": {
-                            "type": "object",
-                            "properties": {}
-                        }
+                "contents": [{ "uri": uri, "mimeType": "application/json", "text": text }]
+            })
+        }
+        "resources/list" => {
+            let tables = if args.tool_allowed("read_table_data") {
+                sorted_table_names(&args.location)
+            } else {
+                Vec::new()
+            };
+            json!({ "resources": resources::table_resources(&tables) })
+        }
+        "resources/templates/list" => json!({ "resourceTemplates": resources::templates() }),
+        "metrics" => {
+            let reset = req
+                .params
+                .as_ref()
+                .and_then(|p| p.get("reset"))
+                .and_then(|r| r.as_bool())
+                == Some(true);
+            metrics::snapshot(reset)
+        }
+        "tools/list" => {
+            let mut tools = tool_definitions(args);
+            tools.retain(|t| args.tool_allowed(t["name"].as_str().unwrap_or_default()));
+            json!({ "tools": tools })
+        }
+        "tools/call" => {
+            let params = req
+                .params
+                .as_ref()
+                .and_then(|p| p.as_object())
+                .ok_or_else(|| RpcError::invalid_params("Missing params"))?;
+            let name = params
+                .get("name")
+                .and_then(|n| n.as_str())
+                .ok_or_else(|| RpcError::invalid_params("Missing tool name"))?;
+            log::debug!(
+                "Handling tool call: {} (from {})",
+                name,
+                state.session.client().label()
+            );
+            let empty_map = Map::new();
+            let arguments = match params.get("arguments") {
+                None | Some(Value::Null) => &empty_map,
+                Some(Value::Object(arguments)) => arguments,
+                Some(_) => return Err(RpcError::invalid_params("arguments must be an object")),
+            };
+
+            let result = call_tool(name, arguments, state)?;
+            // Unknown names share one counter so clients can't grow the table.
+            let counted = if tool_definitions(args).iter().any(|t| t["name"] == name) {
+                name
+            } else {
+                "unknown"
+            };
+            metrics::record_call(
+                counted,
+                result.get("isError").and_then(|e| e.as_bool()) == Some(true),
+            );
+            if resources::LINKABLE_TOOLS.contains(&name)
+                && args.capability_enabled(Capability::Resources)
+                && arguments.get("as_resource_link").and_then(|v| v.as_bool()) == Some(true)
+            {
+                resources::link_response(name, arguments, result, state)
+            } else {
+                result
+            }
+        }
+        _ => {
+            log::debug!("Unknown method: {:?}", req.method);
+            return Err(RpcError::method_not_found(&req.method));
+        }
+    };
+    Ok(result)
+}
+
+/// The body of a `paradox://table/<name>` resource: the schema and the
+/// first 100 records as one JSON document. Tool errors pass through.
+fn table_preview(table_name: &str, args: &Args, opts: &ReadOptions) -> Value {
+    let payload = |result: &Value| {
+        result
+            .get("content")
+            .and_then(|c| c.as_array())
+            .and_then(|c| c.last())
+            .and_then(|block| block.get("text"))
+            .and_then(|t| t.as_str())
+            .and_then(|t| serde_json::from_str::<Value>(t).ok())
+            .unwrap_or(Value::Null)
+    };
+    let schema = handle_read_schema(table_name, args);
+    if schema.get("isError").and_then(|e| e.as_bool()) == Some(true) {
+        return schema;
+    }
+    let rows = handle_read_data(table_name, args, 0, 100, opts, &FieldLayout::default());
+    if rows.get("isError").and_then(|e| e.as_bool()) == Some(true) {
+        return rows;
+    }
+    let text = serde_json::to_string_pretty(&json!({
+        "schema": payload(&schema),
+        "rows": payload(&rows)
+    }))
+    .unwrap();
+    json!({ "content": [{ "type": "text", "text": text }] })
+}
+
+/// Handles a message without an id. Nothing is ever sent back; requests
+/// other than `notifications/*` still run for their side effects.
+pub fn handle_notification(req: &RpcRequest, state: &ServerState) {
+    match req.method.as_str() {
+        "notifications/initialized" => {
+            if !state.session.mark_initialized() {
+                log::debug!("Ignoring repeated notifications/initialized");
+                return;
+            }
+            // Deferred until the handshake completes so a bad location is
+            // reported once, not on every tool call.
+            let locations = &state.args.location;
+            log::info!(
+                "Session initialized for {} ({} tables in {})",
+                state.session.client().label(),
+                sorted_table_names(locations).len(),
+                locations.join(", ")
+            );
+            for location in locations {
+                if let Err(e) = std::fs::read_dir(location) {
+                    log::warn!("Location {} is not readable: {}", location, e);
+                }
+            }
+        }
+        method if method.starts_with("notifications/") => {
+            log::debug!("Ignoring notification: {}", method)
+        }
+        method => {
+            if let Err(e) = handle_request(req, state) {
+                log::debug!("Notification {} failed: {}", method, e.message);
+            }
+        }
+    }
+}
+
+/// Every tool this server implements, as `tools/list` describes them
+/// before `--allow-tool`/`--deny-tool` filtering.
+fn tool_definitions(args: &Args) -> Vec<Value> {
+    let Value::Array(tools) = json!([
+        {
+            "name": "get_server_status",
+            "description": "Get the status and configuration of the Paradox MCP server",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            }
+        },
+        {
+            "name": "list_tables",
+            "description": "List all Paradox tables (.db files) in the configured location, sorted by name",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "offset": {
+                        "type": "integer",
+                        "description": "Number of tables to skip (default: 0)",
+                        "default": 0
                     },
-                    {
-                        "name": "list_tables",
-                        "description": "List all Paradox tables (.db files) in the configured location",
-                        "inputSchema": {
-                            "type": "object",
-                            "properties": {}
-                        }
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of tables to list (default: 1000)",
+                        "default": 1000
                     },
-                    {
-                        "name": "read_table_schema",
-                        "description": "Read the schema (field names and types) of a Paradox table",
-                        "inputSchema": {
-                            "type": "object",
-                            "properties": {
-                                "table_name": {
-                                    "type": "string",
-                                    "description": "The name of the table (e.g., 'customers')"
-                                }
-                            },
-                            "required": ["table_name"]
-                        }
+                    "writable_only": {
+                        "type": "boolean",
+                        "description": "List only tables this server can write: editing permitted, file writable on disk and not held by another call"
                     },
-                    {
-                        "name": "read_table_data",
-                        "description": "Read records from a Paradox table",
-                        "inputSchema": {
-                            "type": "object",
-                            "properties": {
-                                "table_name": {
-                                    "type": "string",
-                                    "description": "The name of the table"
-                                },
-                                "limit": {
-                                    "type": "integer",
-                                    "description": "Maximum number of records to read (default: 100)",
-                                    "default": 100
-                                }
-                            },
-                            "required": ["table_name"]
-                        }
+                    "detailed": {
+                        "type": "boolean",
+                        "description": "Also return each listed table's record count, file size and modification time. Opens every listed table, so it is slower"
+                    }
+                }
+            }
+        },
+        {
+            "name": "read_table_schema",
+            "description": "Read the schema (field names and types) of a Paradox table",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table (e.g., 'customers')"
                     },
-                    {
-                        "name": "search_table",
-                        "description": "Search for specific records in a Paradox table by field values",
-                        "inputSchema": {
-                            "type": "object",
-                            "properties": {
-                                "table_name": {
-                                    "type": "string",
-                                    "description": "The name of the table"
-                                },
-                                "query": {
-                                    "type": "object",
-                                    "description": "Field-value pairs to match (e.g., {\"ID\": \"123\"})"
-                                }
-                            },
-                            "required": ["table_name", "query"]
-                        }
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    }
+                }
+            }
+        },
+        {
+            "name": "get_table_info",
+            "description": "Get an overview of a Paradox table: record and field counts, record size, codepage, and size and created/modified times of the .db and its .mb blob file",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
                     },
-                    {
-                        "name": "create_table",
-                        "description": "Create a new Paradox table with a specific schema (requires editing permission)",
-                        "inputSchema": {
-                            "type": "object",
-                            "properties": {
-                                "table_name": {
-                                    "type": "string",
-                                    "description": "The name of the table to create (e.g., 'new_table')"
-                                },
-                                "fields": {
-                                    "type": "array",
-                                    "items": {
-                                        "type": "object",
-                                        "properties": {
-                                            "name": { "type": "string", "description": "Field name" },
-                                            "type": { "type": "string", "description": "Field type (ALPHA, SHORT, LONG, NUMBER, DATE, LOGICAL, etc.)" },
-                                            "length": { "type": "integer", "description": "Length for ALPHA fields" }
-                                        },
-                                        "required": ["name", "type"]
-                                    },
-                                    "description": "Array of field definitions"
-                                }
-                            },
-                            "required": ["table_name", "fields"]
-                        }
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    }
+                }
+            }
+        },
+        {
+            "name": "count_records",
+            "description": "Count the records in a Paradox table without reading them",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    }
+                }
+            }
+        },
+        {
+            "name": "read_table_data",
+            "description": "Read records from a Paradox table",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Number of records to skip (default: 0); responses carry nextOffset while more records remain",
+                        "default": 0
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": format!(
+                            "Maximum number of records to read (default: {}, at most {})",
+                            args.default_limit, args.max_limit
+                        ),
+                        "default": args.default_limit
+                    },
+                    "coerce_numeric_alpha": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "ALPHA fields whose cleanly numeric values should be returned as JSON numbers"
+                    },
+                    "offset_mode": {
+                        "type": "string",
+                        "enum": ["cumulative", "recordsize"],
+                        "description": "How field offsets are computed when the record size doesn't match the summed field lengths: 'cumulative' (default) packs fields from the start of the record, 'recordsize' packs them against its end"
+                    },
+                    "field_offsets": {
+                        "type": "object",
+                        "additionalProperties": { "type": "integer" },
+                        "description": "Explicit byte offsets within the record for individual fields, overriding offset_mode"
+                    },
+                    "as_resource_link": {
+                        "type": "boolean",
+                        "description": "Return a paradox://result/ resource link to fetch via resources/read instead of inlining the data"
+                    }
+                }
+            }
+        },
+        {
+            "name": "read_sorted",
+            "description": "Read records from a Paradox table sorted by its primary key fields (falls back to physical order with a warning when the table has no primary key)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Number of records to skip in key order (default: 0)",
+                        "default": 0
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": format!(
+                            "Maximum number of records to read (default: {}, at most {})",
+                            args.default_limit, args.max_limit
+                        ),
+                        "default": args.default_limit
+                    },
+                    "coerce_numeric_alpha": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "ALPHA fields whose cleanly numeric values should be returned as JSON numbers"
+                    },
+                    "as_resource_link": {
+                        "type": "boolean",
+                        "description": "Return a paradox://result/ resource link to fetch via resources/read instead of inlining the data"
+                    }
+                }
+            }
+        },
+        {
+            "name": "key_range",
+            "description": "Read the records whose primary key lies in an inclusive range, in key order. For composite keys the range applies to the first key field. A table without a primary key is scanned in full and filtered on `field` (default: its first field), with a warning.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "from_key": {
+                        "description": "Lowest key to include; strings compare lexicographically, numbers numerically"
+                    },
+                    "to_key": {
+                        "description": "Highest key to include"
+                    },
+                    "field": {
+                        "type": "string",
+                        "description": "Field to range on instead of the first key field; mainly for tables without a primary key"
+                    },
+                    "coerce_numeric_alpha": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "ALPHA fields whose cleanly numeric values should be returned as JSON numbers"
+                    }
+                },
+                "required": ["from_key", "to_key"]
+            }
+        },
+        {
+            "name": "export_table_csv",
+            "description": "Export all records of a Paradox table as CSV with a header row of field names, inline or to a file",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "output_path": {
+                        "type": "string",
+                        "description": "Write the CSV to this file, inside the server's location, instead of returning it (requires editing permission)"
+                    },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "Replace output_path if it already exists (default: false)",
+                        "default": false
+                    }
+                }
+            }
+        },
+        {
+            "name": "search_table",
+            "description": "Search for specific records in a Paradox table by field values. _meta reports whether the scan completed or where it stopped (at `limit` results or --max-scan-records examined records)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "query": {
+                        "type": "object",
+                        "description": "Field-value pairs to match (e.g., {\"ID\": \"123\"}); text matches partially. A value can instead be an object of operators, all of which must hold: $eq, $ne, $gt, $gte, $lt, $lte (e.g., {\"Amount\": {\"$gt\": 100}}); $regex tests a regular expression against the field's text (e.g., {\"Name\": {\"$regex\": \"^Acme\"}}); unknown operators match nothing"
+                    },
+                    "case_sensitive": {
+                        "type": "boolean",
+                        "description": "Match plain text values without ignoring case (default: false)",
+                        "default": false
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Number of matching records to skip (default: 0)",
+                        "default": 0
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of matching records to return (default: 1000); _meta.limitReached and nextOffset tell when more may follow",
+                        "default": 1000
+                    },
+                    "coerce_numeric_alpha": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "ALPHA fields whose cleanly numeric values should be returned as JSON numbers"
                     },
-                    {
-                        "name": "insert_record",
-                        "description": "Add a new record to a Paradox table (requires editing permission)",
-                        "inputSchema": {
+                    "as_resource_link": {
+                        "type": "boolean",
+                        "description": "Return a paradox://result/ resource link to fetch via resources/read instead of inlining the data"
+                    }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "query_table",
+            "description": "Filter, sort, page, project or aggregate a Paradox table's records in one call. Execution order: scan -> filter -> aggregate (if given; returns only the aggregates) or order_by -> offset/limit -> select. Without order_by or aggregate the scan stops once offset + limit rows have matched; order_by keeps every matching row in memory until sorted, while aggregates use constant memory.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "filter": {
+                        "type": "object",
+                        "description": "Field-value pairs to match, as in search_table's query"
+                    },
+                    "select": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Fields to return, in this order (default: all)"
+                    },
+                    "order_by": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Sort keys, each a field name optionally followed by 'asc' or 'desc' (e.g., [\"City\", \"Amount desc\"]); blanks sort last"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Number of matching records to skip (default: 0)",
+                        "default": 0
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of records to return (default: 100)",
+                        "default": 100
+                    },
+                    "aggregate": {
+                        "type": "array",
+                        "items": {
                             "type": "object",
                             "properties": {
-                                "table_name": {
-                                    "type": "string",
-                                    "description": "The name of the table"
-                                },
-                                "record": {
-                                    "type": "object",
-                                    "description": "The record data to insert"
-                                }
+                                "op": { "type": "string", "enum": ["count", "sum", "avg", "min", "max"] },
+                                "field": { "type": "string", "description": "Numeric field; optional for count, which then counts records" }
                             },
-                            "required": ["table_name", "record"]
-                        }
+                            "required": ["op"]
+                        },
+                        "description": "Aggregates computed over the filtered records instead of returning them"
+                    },
+                    "coerce_numeric_alpha": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "ALPHA fields whose cleanly numeric values should be returned as JSON numbers"
+                    }
+                }
+            }
+        },
+        {
+            "name": "multi_count",
+            "description": "Count the records matching each of several named queries in a single scan of a Paradox table",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "queries": {
+                        "type": "object",
+                        "additionalProperties": { "type": "object" },
+                        "description": "Named queries, each matched like search_table's query (e.g., {\"open\": {\"Status\": \"open\"}, \"closed\": {\"Status\": \"closed\"}})"
+                    }
+                },
+                "required": ["queries"]
+            }
+        },
+        {
+            "name": "distinct_values",
+            "description": "List the distinct values of one field of a Paradox table, sorted, with how many records hold each",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "field_name": {
+                        "type": "string",
+                        "description": "The field whose values are collected"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of distinct values to return (default: all); _meta.distinct still counts every one"
+                    },
+                    "coerce_numeric_alpha": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "ALPHA fields whose cleanly numeric values should be returned as JSON numbers"
+                    }
+                },
+                "required": ["field_name"]
+            }
+        },
+        {
+            "name": "aggregate",
+            "description": "Compute a single count, sum, average, minimum or maximum over one field of a Paradox table, optionally over the records matching a query; blank values are skipped",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "field": {
+                        "type": "string",
+                        "description": "The field to aggregate; must be numeric for sum, avg, min and max"
+                    },
+                    "op": {
+                        "type": "string",
+                        "enum": ["count", "sum", "avg", "min", "max"],
+                        "description": "The aggregate to compute; count counts the non-blank values"
+                    },
+                    "query": {
+                        "type": "object",
+                        "description": "Field-value pairs to match, as in search_table's query (default: every record)"
+                    }
+                },
+                "required": ["field", "op"]
+            }
+        },
+        {
+            "name": "inspect_value",
+            "description": "Check how a value would be stored in a field and read back, without writing to the table",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "field": {
+                        "type": "string",
+                        "description": "The field whose type is used for the round-trip"
+                    },
+                    "value": {
+                        "description": "The JSON value to probe"
+                    }
+                },
+                "required": ["table_name", "field", "value"]
+            }
+        },
+        {
+            "name": "prepare_insert",
+            "description": "Return a blank record skeleton for insert_record, with a placeholder and format guidance for every writable field",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    }
+                },
+                "required": ["table_name"]
+            }
+        },
+        {
+            "name": "create_table",
+            "description": "Create a new Paradox table with a specific schema (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table to create (e.g., 'new_table')"
                     },
-                    {
-                        "name": "update_record",
-                        "description": "Update an existing record in a Paradox table (requires editing permission)",
-                        "inputSchema": {
+                    "fields": {
+                        "type": "array",
+                        "items": {
                             "type": "object",
                             "properties": {
-                                "table_name": {
-                                    "type": "string",
-                                    "description": "The name of the table"
-                                },
-                                "index": {
-                                    "type": "integer",
-                                    "description": "The 0-based index of the record to update"
-                                },
-                                "record": {
-                                    "type": "object",
-                                    "description": "The new record data"
-                                }
+                                "name": { "type": "string", "description": "Field name" },
+                                "type": { "type": "string", "description": "Field type (ALPHA, SHORT, LONG, NUMBER, DATE, LOGICAL, etc.)" },
+                                "length": { "type": "integer", "description": "Length for ALPHA fields" },
+                                "decimals": { "type": "integer", "description": "Decimal places for BCD fields (0-32, default 0)" }
                             },
-                            "required": ["table_name", "index", "record"]
-                        }
+                            "required": ["name", "type"]
+                        },
+                        "description": "Array of field definitions"
+                    },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "Replace an existing table of the same name; its files are renamed to .bak backups first"
+                    },
+                    "primary_key_fields": {
+                        "type": "integer",
+                        "description": "Make the first N fields the primary key and write its .px index (default: 0, no key)",
+                        "default": 0
                     }
-                ]
-            })
-        }
-        "tools/call" => {
-            if let Some(params) = &req.params {
-                if let Some(name) = params.get("name").and_then(|n| n.as_str()) {
-                    eprintln!("DEBUG: Handling tool call: {}", name);
-                    let empty_map = Map::new();
-                    let arguments = params
-                        .get("arguments")
-                        .and_then(|a| a.as_object())
-                        .unwrap_or(&empty_map);
-
-                    match name {
-                        "get_server_status" => {
-                            let text = format!("Paradox Server Configuration:\n- Location: {}\n- Permit Editing: {}", args.location, args.permit_editing);
-                            json!({
-                                "content": [{ "type": "text", "text": text }]
-                            })
-                        }
-                        "list_tables" => handle_list_tables(args),
-                        "read_table_schema" => {
-                            if let Some(table_name) =
-                                arguments.get("table_name").and_then(|t| t.as_str())
-                            {
-                                handle_read_schema(table_name, &args.location)
-                            } else {
-                                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
-                            }
-                        }
-                        "read_table_data" => {
-                            if let Some(table_name) =
-                                arguments.get("table_name").and_then(|t| t.as_str())
-                            {
-                                let limit = arguments
-                                    .get("limit")
-                                    .and_then(|l| l.as_u64())
-                                    .unwrap_or(100)
-                                    as i32;
-                                handle_read_data(table_name, &args.location, limit)
-                            } else {
-                                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
-                            }
-                        }
-                        "search_table" => {
-                            if let Some(table_name) =
-                                arguments.get("table_name").and_then(|t| t.as_str())
-                            {
-                                if let Some(query) =
-                                    arguments.get("query").and_then(|q| q.as_object())
-                                {
-                                    handle_search_table(table_name, &args.location, query)
-                                } else {
-                                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing or invalid query object" }] })
-                                }
-                            } else {
-                                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
-                            }
-                        }
-                        "create_table" => {
-                            if !args.permit_editing {
-                                return json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] });
-                            }
-                            if let Some(table_name) =
-                                arguments.get("table_name").and_then(|t| t.as_str())
-                            {
-                                if let Some(fields) =
-                                    arguments.get("fields").and_then(|f| f.as_array())
-                                {
-                                    handle_create_table(table_name, &args.location, fields)
-                                } else {
-                                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing or invalid fields array" }] })
-                                }
-                            } else {
-                                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
-                            }
-                        }
-                        "insert_record" => {
-                            if !args.permit_editing {
-                                return json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] });
-                            }
-                            if let Some(table_name) =
-                                arguments.get("table_name").and_then(|t| t.as_str())
-                            {
-                                if let Some(record) =
-                                    arguments.get("record").and_then(|r| r.as_object())
-                                {
-                                    handle_write_record(table_name, &args.location, None, record)
-                                } else {
-                                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record object" }] })
-                                }
-                            } else {
-                                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
-                            }
-                        }
-                        "update_record" => {
-                            if !args.permit_editing {
-                                return json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] });
-                            }
-                            if let Some(table_name) =
-                                arguments.get("table_name").and_then(|t| t.as_str())
-                            {
-                                let index = arguments
-                                    .get("index")
-                                    .and_then(|i| i.as_u64())
-                                    .map(|i| i as i32);
-                                if let Some(record) =
-                                    arguments.get("record").and_then(|r| r.as_object())
-                                {
-                                    if let Some(idx) = index {
-                                        handle_write_record(
-                                            table_name,
-                                            &args.location,
-                                            Some(idx),
-                                            record,
-                                        )
-                                    } else {
-                                        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record index" }] })
-                                    }
-                                } else {
-                                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record object" }] })
-                                }
-                            } else {
-                                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
-                            }
-                        }
-                        _ => {
-                            json!({ "isError": true, "content": [{ "type": "text", "text": format!("Tool not found: {}", name) }] })
-                        }
+                },
+                "required": ["table_name", "fields"]
+            }
+        },
+        {
+            "name": "insert_record",
+            "description": "Add a new record, or several with records, to a Paradox table (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "record": {
+                        "type": "object",
+                        "description": "The record data to insert"
+                    },
+                    "records": {
+                        "type": "array",
+                        "items": { "type": "object" },
+                        "description": "Several records to insert in one call, instead of record; each is reported separately, as in bulk_insert"
+                    }
+                },
+                "required": ["table_name"]
+            }
+        },
+        {
+            "name": "bulk_insert",
+            "description": "Insert several records into a Paradox table in one call, returning each row's index and generated AUTOINC values (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "records": {
+                        "type": "array",
+                        "items": { "type": "object" },
+                        "description": "The records to insert, in order"
                     }
+                },
+                "required": ["table_name", "records"]
+            }
+        },
+        {
+            "name": "import_csv",
+            "description": "Insert the rows of a CSV file into a Paradox table, mapping header columns onto field names; rows that don't convert are reported with their line numbers and skipped (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "csv_text": {
+                        "type": "string",
+                        "description": "The CSV to import, header row first"
+                    },
+                    "input_path": {
+                        "type": "string",
+                        "description": "Alternative to csv_text: a CSV file to read, inside the server's location"
+                    }
+                },
+                "required": ["table_name"]
+            }
+        },
+        {
+            "name": "update_record",
+            "description": "Update an existing record in a Paradox table (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "index": {
+                        "type": "integer",
+                        "description": "The 0-based index of the record to update"
+                    },
+                    "record": {
+                        "type": "object",
+                        "description": "The fields to change; a null value clears the field to blank"
+                    }
+                },
+                "required": ["table_name", "index", "record"]
+            }
+        },
+        {
+            "name": "delete_record",
+            "description": "Delete a record from a Paradox table; later records move down by one index (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "index": {
+                        "type": "integer",
+                        "description": "The 0-based index of the record to delete"
+                    }
+                },
+                "required": ["table_name", "index"]
+            }
+        },
+        {
+            "name": "update_by_query",
+            "description": "Apply the same changes to every record matching a search_table-style query (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "query": {
+                        "type": "object",
+                        "description": "Selects the records to update, as in search_table"
+                    },
+                    "record": {
+                        "type": "object",
+                        "description": "The fields to change; a null value clears the field to blank"
+                    },
+                    "max_affected": {
+                        "type": "integer",
+                        "description": "Refuse the update, changing nothing, if more records than this match (default: 100)",
+                        "default": 100
+                    }
+                },
+                "required": ["table_name", "query", "record"]
+            }
+        },
+        {
+            "name": "delete_by_query",
+            "description": "Delete every record matching a search_table-style query (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "query": {
+                        "type": "object",
+                        "description": "Selects the records to delete, as in search_table"
+                    },
+                    "max_affected": {
+                        "type": "integer",
+                        "description": "Refuse the delete, removing nothing, if more records than this match (default: 100)",
+                        "default": 100
+                    }
+                },
+                "required": ["table_name", "query"]
+            }
+        },
+        {
+            "name": "truncate_table",
+            "description": "Delete every record in a Paradox table, keeping its schema (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Must be true, as a guard against emptying a table by accident"
+                    }
+                },
+                "required": ["table_name", "confirm"]
+            }
+        },
+        {
+            "name": "table_exists",
+            "description": "Check whether a Paradox table exists, without opening it",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    }
+                }
+            }
+        },
+        {
+            "name": "copy_table",
+            "description": "Copy a Paradox table, its schema, records and .mb blob file, under a new name (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "source": {
+                        "type": "string",
+                        "description": "The name of the table to copy"
+                    },
+                    "destination": {
+                        "type": "string",
+                        "description": "The name of the new table"
+                    },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "Replace an existing destination table, keeping its files as .bak backups"
+                    }
+                },
+                "required": ["source", "destination"]
+            }
+        },
+        {
+            "name": "drop_table",
+            "description": "Delete a Paradox table and its companion files (.px, .mb, .val, .xg*, .yg*) (requires editing permission)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Must be true, as a guard against deleting a table by accident"
+                    }
+                },
+                "required": ["table_name", "confirm"]
+            }
+        },
+        {
+            "name": "get_record",
+            "description": "Read the single record at a given index of a Paradox table",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "table_name": {
+                        "type": "string",
+                        "description": "The name of the table"
+                    },
+                    "table_index": {
+                        "type": "integer",
+                        "description": "Alternative to table_name: 0-based position of the table in the list_tables ordering"
+                    },
+                    "index": {
+                        "type": "integer",
+                        "description": "The 0-based index of the record, as used by update_record and delete_record"
+                    },
+                    "coerce_numeric_alpha": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "ALPHA fields whose cleanly numeric values should be returned as JSON numbers"
+                    }
+                },
+                "required": ["table_name", "index"]
+            }
+        }
+    ]) else {
+        unreachable!("a JSON array literal");
+    };
+    tools
+}
+
+/// Dispatches a `tools/call` request to the matching tool handler.
+fn call_tool(
+    name: &str,
+    arguments: &Map<String, Value>,
+    state: &ServerState,
+) -> Result<Value, RpcError> {
+    let args = &state.args;
+    if !args.tool_allowed(name) {
+        return Ok(ToolError::new(
+            "TOOL_DISABLED",
+            format!("Tool '{}' is disabled on this server.", name),
+        )
+        .into_value());
+    }
+    let result = match name {
+        "get_server_status" => {
+            let client = state.session.client();
+            let text = format!(
+                "Paradox Server Configuration:\n- Location: {}\n- Permit Editing: {}\n- Client: {}",
+                args.location.join(", "),
+                args.permit_editing,
+                client.label()
+            );
+            let (major, minor) =
+                unsafe { (pxlib::PX_get_majorversion(), pxlib::PX_get_minorversion()) };
+            let status = json!({
+                "locations": args.location,
+                "permit_editing": args.permit_editing,
+                "table_count": sorted_table_names(&args.location).len(),
+                "version": env!("CARGO_PKG_VERSION"),
+                "pxlib_version": format!("{}.{}", major, minor)
+            });
+            let mut result = data_response(args, text, &status);
+            result["_meta"] = json!({
+                "client": client.to_value(),
+                "initialized": state.session.is_initialized()
+            });
+            result
+        }
+        "list_tables" => {
+            let offset = usize_arg(arguments, "offset").unwrap_or(0);
+            let limit = usize_arg(arguments, "limit").unwrap_or(1000);
+            let writable_only =
+                arguments.get("writable_only").and_then(|v| v.as_bool()) == Some(true);
+            let detailed = arguments.get("detailed").and_then(|v| v.as_bool()) == Some(true);
+            handle_list_tables(args, offset, limit, writable_only, detailed)
+        }
+        "read_table_schema" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => handle_read_schema(&table_name, args),
+            Err(e) => e,
+        },
+        "count_records" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => handle_count_records(&table_name, args),
+            Err(e) => e,
+        },
+        "get_table_info" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => handle_get_table_info(&table_name, args),
+            Err(e) => e,
+        },
+        "read_table_data" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => {
+                let offset = c_int_arg(arguments, "offset")?.unwrap_or(0);
+                let limit = c_int_arg(arguments, "limit")?
+                    .unwrap_or(args.default_limit)
+                    .min(args.max_limit);
+                handle_read_data(
+                    &table_name,
+                    args,
+                    offset,
+                    limit,
+                    &ReadOptions::from_arguments(arguments, args),
+                    &FieldLayout::from_arguments(arguments)?,
+                )
+            }
+            Err(e) => e,
+        },
+        "read_sorted" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => {
+                let offset = c_int_arg(arguments, "offset")?.unwrap_or(0);
+                let limit = c_int_arg(arguments, "limit")?
+                    .unwrap_or(args.default_limit)
+                    .min(args.max_limit);
+                handle_read_sorted(
+                    &table_name,
+                    args,
+                    offset,
+                    limit,
+                    &ReadOptions::from_arguments(arguments, args),
+                )
+            }
+            Err(e) => e,
+        },
+        "key_range" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => match (arguments.get("from_key"), arguments.get("to_key")) {
+                (Some(from_key), Some(to_key)) => handle_key_range(
+                    &table_name,
+                    args,
+                    from_key,
+                    to_key,
+                    arguments.get("field").and_then(|f| f.as_str()),
+                    &ReadOptions::from_arguments(arguments, args),
+                ),
+                _ => {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing from_key or to_key" }] })
+                }
+            },
+            Err(e) => e,
+        },
+        "export_table_csv" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => match arguments.get("output_path").and_then(|p| p.as_str()) {
+                Some(_) if !args.permit_editing => {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] })
+                }
+                Some(output_path) => handle_export_table_csv_file(
+                    &table_name,
+                    args,
+                    output_path,
+                    arguments.get("overwrite").and_then(|v| v.as_bool()) == Some(true),
+                ),
+                None => handle_export_table_csv(&table_name, args),
+            },
+            Err(e) => e,
+        },
+        "search_table" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => {
+                if let Some(query) = arguments.get("query").and_then(|q| q.as_object()) {
+                    let case_sensitive =
+                        arguments.get("case_sensitive").and_then(|v| v.as_bool()) == Some(true);
+                    let offset = c_int_arg(arguments, "offset")?.unwrap_or(0) as usize;
+                    let limit = c_int_arg(arguments, "limit")?.unwrap_or(1000) as usize;
+                    handle_search_table(
+                        &table_name,
+                        args,
+                        query,
+                        case_sensitive,
+                        offset,
+                        limit,
+                        &ReadOptions::from_arguments(arguments, args),
+                    )
                 } else {
-                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing tool name" }] })
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing or invalid query object" }] })
                 }
-            } else {
-                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing params" }] })
             }
+            Err(e) => e,
+        },
+        "query_table" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => handle_query_table(
+                &table_name,
+                args,
+                &QuerySpec::from_arguments(arguments)?,
+                &ReadOptions::from_arguments(arguments, args),
+            ),
+            Err(e) => e,
+        },
+        "multi_count" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => {
+                let queries = arguments.get("queries").and_then(|q| q.as_object());
+                match queries.filter(|q| q.values().all(|v| v.is_object())) {
+                    Some(queries) => handle_multi_count(&table_name, args, queries),
+                    None => {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing or invalid queries object" }] })
+                    }
+                }
+            }
+            Err(e) => e,
+        },
+        "distinct_values" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => match arguments.get("field_name").and_then(|f| f.as_str()) {
+                Some(field_name) => handle_distinct_values(
+                    &table_name,
+                    args,
+                    field_name,
+                    usize_arg(arguments, "limit"),
+                    &ReadOptions::from_arguments(arguments, args),
+                ),
+                None => {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing field_name" }] })
+                }
+            },
+            Err(e) => e,
+        },
+        "aggregate" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => {
+                let op = arguments
+                    .get("op")
+                    .and_then(|o| o.as_str())
+                    .and_then(AggregateOp::parse)
+                    .ok_or_else(|| {
+                        RpcError::invalid_params("op must be one of count, sum, avg, min or max")
+                    })?;
+                let query = arguments
+                    .get("query")
+                    .and_then(|q| q.as_object())
+                    .cloned()
+                    .unwrap_or_default();
+                match arguments.get("field").and_then(|f| f.as_str()) {
+                    Some(field) => handle_aggregate(&table_name, args, field, op, &query),
+                    None => {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing field" }] })
+                    }
+                }
+            }
+            Err(e) => e,
+        },
+        "inspect_value" => {
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                match (
+                    arguments.get("field").and_then(|f| f.as_str()),
+                    arguments.get("value"),
+                ) {
+                    (Some(field), Some(value)) => {
+                        handle_inspect_value(&table_name, args, field, value)
+                    }
+                    _ => {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing field or value" }] })
+                    }
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "prepare_insert" => {
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                handle_prepare_insert(&table_name, args)
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "create_table" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                if let Some(fields) = arguments.get("fields").and_then(|f| f.as_array()) {
+                    let overwrite =
+                        arguments.get("overwrite").and_then(|v| v.as_bool()) == Some(true);
+                    let primary_keys = c_int_arg(arguments, "primary_key_fields")?.unwrap_or(0);
+                    handle_create_table(&table_name, args, fields, overwrite, primary_keys)
+                } else {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing or invalid fields array" }] })
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "insert_record" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                let record = arguments.get("record").and_then(|r| r.as_object());
+                let records = arguments.get("records").and_then(|r| r.as_array());
+                match (record, records) {
+                    (Some(record), None) => handle_write_record(&table_name, args, None, record),
+                    (None, Some(records)) => handle_bulk_insert(&table_name, args, records),
+                    (Some(_), Some(_)) => {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Pass either record or records, not both" }] })
+                    }
+                    (None, None) => {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record object" }] })
+                    }
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "bulk_insert" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                if let Some(records) = arguments.get("records").and_then(|r| r.as_array()) {
+                    handle_bulk_insert(&table_name, args, records)
+                } else {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing records array" }] })
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "import_csv" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                let csv_text = arguments.get("csv_text").and_then(|t| t.as_str());
+                let input_path = arguments.get("input_path").and_then(|p| p.as_str());
+                match (csv_text, input_path) {
+                    (Some(text), None) => handle_import_csv(&table_name, args, text),
+                    (None, Some(input_path)) => {
+                        match resolve_location_file(input_path, args, "INVALID_INPUT_PATH") {
+                            Ok(path) => match std::fs::read_to_string(&path) {
+                                Ok(text) => handle_import_csv(&table_name, args, &text),
+                                Err(e) => ToolError::new(
+                                    "IMPORT_READ_FAILED",
+                                    format!("Failed to read '{}': {}", path.display(), e),
+                                )
+                                .into_value(),
+                            },
+                            Err(e) => e.into_value(),
+                        }
+                    }
+                    _ => {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Pass exactly one of csv_text or input_path" }] })
+                    }
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "update_record" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                let index = c_int_arg(arguments, "index")?;
+                if let Some(record) = arguments.get("record").and_then(|r| r.as_object()) {
+                    if let Some(idx) = index {
+                        handle_write_record(&table_name, args, Some(idx), record)
+                    } else {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record index" }] })
+                    }
+                } else {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record object" }] })
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "delete_record" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                if let Some(idx) = c_int_arg(arguments, "index")? {
+                    handle_delete_record(&table_name, args, idx)
+                } else {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record index" }] })
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "update_by_query" | "delete_by_query" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            // Only updates take a record; a stray one on delete_by_query is ignored.
+            let patch = arguments
+                .get("record")
+                .and_then(|r| r.as_object())
+                .filter(|_| name == "update_by_query");
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                let max_affected = c_int_arg(arguments, "max_affected")?.unwrap_or(100) as usize;
+                match arguments.get("query").and_then(|q| q.as_object()) {
+                    None => {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing or invalid query object" }] })
+                    }
+                    Some(_) if name == "update_by_query" && patch.is_none() => {
+                        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record object" }] })
+                    }
+                    Some(query) => {
+                        handle_modify_by_query(&table_name, args, query, patch, max_affected)
+                    }
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "truncate_table" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                if arguments.get("confirm").and_then(|c| c.as_bool()) == Some(true) {
+                    handle_truncate_table(&table_name, args)
+                } else {
+                    ToolError::new(
+                        "CONFIRMATION_REQUIRED",
+                        "truncate_table deletes every record; pass confirm: true to proceed.",
+                    )
+                    .into_value()
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        "table_exists" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => handle_table_exists(&table_name, args),
+            Err(e) => e,
+        },
+        "copy_table" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            match (
+                table_arg(arguments, args, "source"),
+                table_arg(arguments, args, "destination"),
+            ) {
+                (Some(source), Some(destination)) => {
+                    let overwrite =
+                        arguments.get("overwrite").and_then(|v| v.as_bool()) == Some(true);
+                    handle_copy_table(&source, &destination, args, overwrite)
+                }
+                _ => {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing source or destination" }] })
+                }
+            }
+        }
+        "get_record" => match resolve_table_arg(arguments, args)? {
+            Ok(table_name) => {
+                if let Some(idx) = c_int_arg(arguments, "index")? {
+                    handle_get_record(
+                        &table_name,
+                        args,
+                        idx,
+                        &ReadOptions::from_arguments(arguments, args),
+                    )
+                } else {
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Missing record index" }] })
+                }
+            }
+            Err(e) => e,
+        },
+        "drop_table" => {
+            if !args.permit_editing {
+                return Ok(
+                    json!({ "isError": true, "content": [{ "type": "text", "text": "Editing is not permitted on this server." }] }),
+                );
+            }
+            if let Some(table_name) = table_name_arg(arguments, args) {
+                if arguments.get("confirm").and_then(|c| c.as_bool()) == Some(true) {
+                    handle_drop_table(&table_name, args)
+                } else {
+                    ToolError::new(
+                        "CONFIRMATION_REQUIRED",
+                        "drop_table deletes the table and its files; pass confirm: true to proceed.",
+                    )
+                    .into_value()
+                }
+            } else {
+                json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] })
+            }
+        }
+        _ => {
+            json!({ "isError": true, "content": [{ "type": "text", "text": format!("Tool not found: {}", name) }] })
+        }
+    };
+    Ok(result)
+}
+
+/// Returns the `.db` file names found in `locations`, whatever the case of
+/// the extension, deduplicated and sorted by name so that positions are
+/// stable across calls (see `table_index`).
+pub fn sorted_table_names(locations: &[String]) -> Vec<String> {
+    let mut tables = Vec::new();
+    for location in locations {
+        if let Ok(entries) = std::fs::read_dir(location) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("db"))
+                {
+                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                        tables.push(name.to_string());
+                    }
+                }
+            }
+        }
+    }
+    tables.sort();
+    tables.dedup();
+    tables
+}
+
+/// Every file `table_name` (as listed by `list_tables`) names across the
+/// locations, in search order; the first is the one tools open.
+pub fn table_paths(args: &Args, table_name: &str) -> Vec<PathBuf> {
+    args.location
+        .iter()
+        .map(|location| Path::new(location).join(table_name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Resolves the target table of a tool call from either `table_name` or,
+/// as a fallback, `table_index` into the sorted `list_tables` ordering. A
+/// `table_index` that isn't a non-negative integer is an invalid-params
+/// error, as for `c_int_arg`; the inner error is the tool result to return.
+fn resolve_table_arg(
+    arguments: &Map<String, Value>,
+    args: &Args,
+) -> Result<Result<String, Value>, RpcError> {
+    if let Some(table_name) = table_name_arg(arguments, args) {
+        return Ok(Ok(table_name));
+    }
+    if let Some(table_index) = c_int_arg(arguments, "table_index")? {
+        let tables = sorted_table_names(&args.location);
+        return Ok(tables.get(table_index as usize).cloned().ok_or_else(|| {
+            ToolError::new(
+                "TABLE_INDEX_OUT_OF_RANGE",
+                format!(
+                    "Table index {} out of range ({} tables found).",
+                    table_index,
+                    tables.len()
+                ),
+            )
+            .into_value()
+        }));
+    }
+    Ok(Err(
+        json!({ "isError": true, "content": [{ "type": "text", "text": "Missing table_name" }] }),
+    ))
+}
+
+/// Maps a `table_name` onto its file in the first location that has it
+/// (the first location, for a table yet to be created), adding `.db` when
+/// it has no extension and refusing any other extension. An exact match in
+/// any location wins over a match that differs only in case. Subdirectories
+/// (`sub/customers`) are fine, but absolute paths, `..` components and
+/// symlinks leading out of a location are refused, so a table name can
+/// never reach other files.
+fn resolve_table_path(table_name: &str, args: &Args) -> Result<PathBuf, ToolError> {
+    let outside = || {
+        ToolError::new(
+            "INVALID_TABLE_NAME",
+            format!(
+                "Table name '{}' must name a table inside the server's location.",
+                table_name
+            ),
+        )
+    };
+    let relative = Path::new(table_name);
+    let lexically_inside = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if table_name.is_empty() || !lexically_inside {
+        return Err(outside());
+    }
+    let mut relative = relative.to_path_buf();
+    match relative.extension() {
+        None => {
+            relative.set_extension("db");
+        }
+        Some(ext) if ext.eq_ignore_ascii_case("db") => {}
+        // Only tables are served, not whatever else shares the directory.
+        Some(_) => {
+            return Err(ToolError::new(
+                "INVALID_TABLE_NAME",
+                format!(
+                    "Table name '{}' must have no extension or the .db extension.",
+                    table_name
+                ),
+            ));
+        }
+    }
+
+    let mut fallback = None;
+    for location in &args.location {
+        let full_path = Path::new(location).join(&relative);
+        // Compare canonical forms of the deepest part that exists, which is
+        // the parent directory for a table that's about to be created.
+        if let Ok(root) = std::fs::canonicalize(location) {
+            let existing = if full_path.exists() {
+                Some(full_path.as_path())
+            } else {
+                full_path.parent()
+            };
+            if let Some(Ok(resolved)) = existing.map(std::fs::canonicalize) {
+                if !resolved.starts_with(&root) {
+                    return Err(outside());
+                }
+            }
+        }
+        if full_path.exists() {
+            return Ok(full_path);
+        }
+        fallback.get_or_insert(full_path);
+    }
+    // On case-sensitive filesystems, `customers` should still find a table
+    // saved as `CUSTOMERS.DB`.
+    for location in &args.location {
+        let full_path = Path::new(location).join(&relative);
+        if let Some(found) = find_ignoring_case(&full_path, table_name)? {
+            if let (Ok(root), Ok(resolved)) = (
+                std::fs::canonicalize(location),
+                std::fs::canonicalize(&found),
+            ) {
+                if !resolved.starts_with(&root) {
+                    return Err(outside());
+                }
+            }
+            return Ok(found);
+        }
+    }
+    fallback.ok_or_else(outside)
+}
+
+/// Maps a file argument such as `output_path` onto a path inside the first
+/// location, with the same containment rules as `resolve_table_path`:
+/// absolute paths, `..` components and symlinks leading out of every
+/// location are refused with `code`.
+fn resolve_location_file(
+    file_path: &str,
+    args: &Args,
+    code: &'static str,
+) -> Result<PathBuf, ToolError> {
+    let outside = || {
+        ToolError::new(
+            code,
+            format!(
+                "Path '{}' must name a file inside the server's location.",
+                file_path
+            ),
+        )
+    };
+    let relative = Path::new(file_path);
+    let lexically_inside = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if file_path.is_empty() || !lexically_inside {
+        return Err(outside());
+    }
+    let full_path = args.primary_location().join(relative);
+    let existing = if full_path.exists() {
+        Some(full_path.as_path())
+    } else {
+        full_path.parent()
+    };
+    let resolved = existing
+        .and_then(|p| std::fs::canonicalize(p).ok())
+        .ok_or_else(outside)?;
+    let inside = args.location.iter().any(|location| {
+        std::fs::canonicalize(location).is_ok_and(|root| resolved.starts_with(root))
+    });
+    if !inside {
+        return Err(outside());
+    }
+    Ok(full_path)
+}
+
+/// Looks in `path`'s directory for a file whose name matches `path`'s
+/// ignoring ASCII case. Several such files make `table_name` ambiguous
+/// rather than picking one of them.
+fn find_ignoring_case(path: &Path, table_name: &str) -> Result<Option<PathBuf>, ToolError> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str())) else {
+        return Ok(None);
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(None);
+    };
+    let mut found: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.is_file()
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.eq_ignore_ascii_case(name))
+        })
+        .collect();
+    if found.len() > 1 {
+        found.sort();
+        return Err(ToolError::new(
+            "AMBIGUOUS_TABLE_NAME",
+            format!(
+                "Table name '{}' matches several files that differ only by case: {}.",
+                table_name,
+                found
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ));
+    }
+    Ok(found.pop())
+}
+
+/// Reads `table_name`, turning backslashes into the platform separator when
+/// `--accept-windows-paths` is set.
+fn table_name_arg(arguments: &Map<String, Value>, args: &Args) -> Option<String> {
+    table_arg(arguments, args, "table_name")
+}
+
+/// Reads the table name in argument `key`, like `table_name_arg`.
+fn table_arg(arguments: &Map<String, Value>, args: &Args, key: &str) -> Option<String> {
+    let table_name = arguments.get(key).and_then(|t| t.as_str())?;
+    if args.accept_windows_paths {
+        Some(table_name.replace('\\', std::path::MAIN_SEPARATOR_STR))
+    } else {
+        Some(table_name.to_string())
+    }
+}
+
+/// Reads an optional non-negative integer argument, rejecting negative,
+/// fractional or non-numeric values and those that don't fit the `c_int`
+/// range pxlib uses for record counts and positions. `null` counts as absent.
+fn c_int_arg(
+    arguments: &Map<String, Value>,
+    key: &str,
+) -> Result<Option<std::os::raw::c_int>, RpcError> {
+    let Some(value) = arguments.get(key).filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    match value.as_u64() {
+        None => Err(RpcError::invalid_params(format!(
+            "Argument '{}' must be a non-negative integer, got {}",
+            key, value
+        ))),
+        Some(v) => std::os::raw::c_int::try_from(v).map(Some).map_err(|_| {
+            RpcError::invalid_params(format!(
+                "Argument '{}' is out of range: {} exceeds {}",
+                key,
+                v,
+                std::os::raw::c_int::MAX
+            ))
+        }),
+    }
+}
+
+/// Builds a data tool result: a human-readable summary block followed by the
+/// JSON payload. With `--json-output-only` only the JSON block is emitted.
+fn data_response<T: serde::Serialize + ?Sized>(args: &Args, summary: String, data: &T) -> Value {
+    let payload = serde_json::to_string_pretty(data).unwrap();
+    if args.json_output_only {
+        json!({ "content": [{ "type": "text", "text": payload }] })
+    } else {
+        json!({
+            "content": [
+                { "type": "text", "text": summary },
+                { "type": "text", "text": payload }
+            ]
+        })
+    }
+}
+
+/// Per-call options shaping how decoded records are rendered.
+struct ReadOptions {
+    /// ALPHA fields whose cleanly numeric values are emitted as JSON numbers.
+    coerce_numeric_alpha: Vec<String>,
+    /// Key order of the record object, from `--response-field-order`.
+    field_order: FieldOrder,
+    /// Render LONG/AUTOINC values beyond 2^53 as strings, from `--big-int-as-string`.
+    big_int_as_string: bool,
+    /// TIME representation, from `--date-format`.
+    date_format: DateFormat,
+    /// Render whole NUMBER values as integers, from `--integral-numbers-as-int`.
+    integral_numbers_as_int: bool,
+    /// Longest BLOB/BYTES content returned, from `--max-blob-bytes`.
+    max_blob_bytes: usize,
+}
+
+/// Largest integer a JavaScript number (IEEE-754 double) represents exactly.
+const JS_MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+impl ReadOptions {
+    fn from_arguments(arguments: &Map<String, Value>, args: &Args) -> Self {
+        ReadOptions {
+            coerce_numeric_alpha: string_list_arg(arguments, "coerce_numeric_alpha"),
+            field_order: args.response_field_order.clone(),
+            big_int_as_string: args.big_int_as_string,
+            date_format: args.date_format,
+            integral_numbers_as_int: args.integral_numbers_as_int,
+            max_blob_bytes: args.max_blob_bytes,
+        }
+    }
+
+    /// Applies the rendering options to a decoded record in place.
+    fn apply(&self, record: &mut Map<String, Value>, fields: &[pxlib::pxfield_t]) {
+        self.coerce_alpha(record, fields);
+        self.stringify_big_ints(record, fields);
+        self.render_dates_and_times(record, fields);
+        self.annotate_binary(record, fields);
+        if self.integral_numbers_as_int {
+            integral_numbers_as_int(record, fields);
+        }
+        self.reorder(record);
+    }
+
+    fn render_dates_and_times(&self, record: &mut Map<String, Value>, fields: &[pxlib::pxfield_t]) {
+        if self.date_format == DateFormat::Iso {
+            return;
+        }
+        for f in fields {
+            let render = match f.px_ftype as u32 {
+                pxlib::pxfTime => dates::render_time,
+                pxlib::pxfDate => dates::render_date,
+                pxlib::pxfTimestamp => dates::render_timestamp,
+                _ => continue,
+            };
+            let name = unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy();
+            if let Some(val) = record.get_mut(name.as_ref()) {
+                *val = render(val, self.date_format);
+            }
+        }
+    }
+
+    /// Adds a `<field>_bytes` length next to each base64 BLOB/BYTES value
+    /// and cuts values beyond `max_blob_bytes`, flagging them with
+    /// `<field>_truncated`.
+    fn annotate_binary(&self, record: &mut Map<String, Value>, fields: &[pxlib::pxfield_t]) {
+        for f in fields {
+            let ftype = f.px_ftype as u32;
+            if ftype != pxlib::pxfBLOb && ftype != pxlib::pxfBytes {
+                continue;
+            }
+            let name = unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy();
+            let Some(Value::String(encoded)) = record.get_mut(name.as_ref()) else {
+                continue;
+            };
+            let len = base64::decoded_len(encoded);
+            let truncated = base64::truncate(encoded, self.max_blob_bytes);
+            record.insert(format!("{}_bytes", name), json!(len));
+            if truncated {
+                record.insert(format!("{}_truncated", name), Value::Bool(true));
+            }
+        }
+    }
+
+    /// Keeps integer keys exact for JavaScript clients, which would round
+    /// anything past `JS_MAX_SAFE_INTEGER` when parsing the response.
+    fn stringify_big_ints(&self, record: &mut Map<String, Value>, fields: &[pxlib::pxfield_t]) {
+        if !self.big_int_as_string {
+            return;
+        }
+        for f in fields {
+            let ftype = f.px_ftype as u32;
+            if ftype != pxlib::pxfLong && ftype != pxlib::pxfAutoInc {
+                continue;
+            }
+            let name = unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy();
+            if let Some(val) = record.get_mut(name.as_ref()) {
+                if let Some(n) = val.as_i64() {
+                    if n.unsigned_abs() > JS_MAX_SAFE_INTEGER as u64 {
+                        *val = Value::String(n.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rearranges the keys of a record decoded in schema order.
+    fn reorder(&self, record: &mut Map<String, Value>) {
+        match &self.field_order {
+            FieldOrder::Schema => {}
+            FieldOrder::Alpha => record.sort_keys(),
+            FieldOrder::Custom(names) => {
+                let mut ordered = Map::new();
+                for name in names {
+                    if let Some(val) = record.shift_remove(name) {
+                        ordered.insert(name.clone(), val);
+                    }
+                }
+                ordered.append(record);
+                *record = ordered;
+            }
+        }
+    }
+
+    fn coerce_alpha(&self, record: &mut Map<String, Value>, fields: &[pxlib::pxfield_t]) {
+        if self.coerce_numeric_alpha.is_empty() {
+            return;
+        }
+        for f in fields {
+            if f.px_ftype as u32 != pxlib::pxfAlpha {
+                continue;
+            }
+            let name = unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy();
+            if !self.coerce_numeric_alpha.iter().any(|n| n == name.as_ref()) {
+                continue;
+            }
+            if let Some(val) = record.get_mut(name.as_ref()) {
+                if let Some(num) = val.as_str().and_then(parse_clean_number) {
+                    *val = num;
+                }
+            }
+        }
+    }
+}
+
+/// How `read_table_data` locates fields within a record, for tables whose
+/// record size doesn't match the summed field lengths.
+#[derive(Default)]
+struct FieldLayout {
+    /// Pack fields against the end of the record instead of its start,
+    /// for tables with leading rather than trailing slack.
+    from_end: bool,
+    /// Explicit byte offsets for individual fields.
+    field_offsets: Map<String, Value>,
+}
+
+impl FieldLayout {
+    fn from_arguments(arguments: &Map<String, Value>) -> Result<Self, RpcError> {
+        let from_end = match arguments.get("offset_mode").and_then(|m| m.as_str()) {
+            None | Some("cumulative") => false,
+            Some("recordsize") => true,
+            Some(other) => {
+                return Err(RpcError::invalid_params(format!(
+                    "Unknown offset_mode '{}'; expected 'cumulative' or 'recordsize'",
+                    other
+                )))
+            }
+        };
+        let field_offsets = arguments
+            .get("field_offsets")
+            .and_then(|o| o.as_object())
+            .cloned()
+            .unwrap_or_default();
+        Ok(FieldLayout {
+            from_end,
+            field_offsets,
+        })
+    }
+
+    fn is_default(&self) -> bool {
+        !self.from_end && self.field_offsets.is_empty()
+    }
+
+    /// Computes each field's start offset, rejecting any field that would
+    /// extend past the end of the record.
+    fn offsets(
+        &self,
+        fields: &[pxlib::pxfield_t],
+        record_size: usize,
+    ) -> Result<Vec<usize>, ToolError> {
+        if let Some(f) = fields.iter().find(|f| f.px_flen < 0) {
+            let name = unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy();
+            return Err(ToolError::new(
+                "INVALID_FIELD_OFFSET",
+                format!(
+                    "Field '{}' has a negative length {} in the table header.",
+                    name, f.px_flen
+                ),
+            ));
+        }
+        let mut offsets = cumulative_offsets(fields);
+        if self.from_end {
+            let summed: usize = fields.iter().map(|f| f.px_flen as usize).sum();
+            let Some(shift) = record_size.checked_sub(summed) else {
+                return Err(ToolError::new(
+                    "INVALID_FIELD_OFFSET",
+                    format!(
+                        "offset_mode 'recordsize' needs the record size ({}) to be at least the summed field lengths ({}).",
+                        record_size, summed
+                    ),
+                ));
+            };
+            for offset in &mut offsets {
+                *offset += shift;
+            }
+        }
+
+        for (f, offset) in fields.iter().zip(offsets.iter_mut()) {
+            let name = unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy();
+            if let Some(explicit) = self.field_offsets.get(name.as_ref()) {
+                *offset = explicit.as_u64().ok_or_else(|| {
+                    ToolError::new(
+                        "INVALID_FIELD_OFFSET",
+                        format!(
+                            "Offset for field '{}' must be a non-negative integer.",
+                            name
+                        ),
+                    )
+                })? as usize;
+            }
+            let end = offset.checked_add(f.px_flen as usize);
+            if end.is_none_or(|end| end > record_size) {
+                return Err(ToolError::new(
+                    "INVALID_FIELD_OFFSET",
+                    format!(
+                        "Field '{}' at offset {} with length {} extends past the record size {}.",
+                        name, offset, f.px_flen, record_size
+                    ),
+                ));
+            }
+        }
+
+        if let Some(unknown) = self.field_offsets.keys().find(|k| {
+            !fields.iter().any(|f| {
+                unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy() == k.as_str()
+            })
+        }) {
+            return Err(ToolError::new(
+                "INVALID_FIELD_OFFSET",
+                format!("field_offsets names unknown field '{}'.", unknown),
+            ));
+        }
+        Ok(offsets)
+    }
+}
+
+/// Turns NUMBER values without a fractional part into JSON integers, so a
+/// whole-number column reads `123` rather than `123.0`.
+fn integral_numbers_as_int(record: &mut Map<String, Value>, fields: &[pxlib::pxfield_t]) {
+    for f in fields {
+        if f.px_ftype as u32 != pxlib::pxfNumber {
+            continue;
+        }
+        let name = unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy();
+        if let Some(val) = record.get_mut(name.as_ref()) {
+            if let Some(v) = val.as_f64() {
+                // Beyond 2^53 a double can't hold a fraction anyway, and the
+                // i64 cast would saturate; keep those as they are.
+                if v.fract() == 0.0 && v.abs() <= JS_MAX_SAFE_INTEGER as f64 {
+                    *val = json!(v as i64);
+                }
+            }
+        }
+    }
+}
+
+/// Parses a string as a JSON number only when it is cleanly numeric: an
+/// optional sign, digits and an optional fraction. Values with leading zeros
+/// (e.g. zip codes) are left alone since the conversion would lose them.
+fn parse_clean_number(s: &str) -> Option<Value> {
+    let s = s.trim();
+    let unsigned = s.strip_prefix('-').unwrap_or(s);
+    let (int_part, frac_part) = match unsigned.split_once('.') {
+        Some((i, f)) => (i, Some(f)),
+        None => (unsigned, None),
+    };
+    if int_part.is_empty() || !int_part.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if int_part.len() > 1 && int_part.starts_with('0') {
+        return None;
+    }
+    match frac_part {
+        None => s.parse::<i64>().ok().map(Value::from),
+        Some(f) if !f.is_empty() && f.bytes().all(|b| b.is_ascii_digit()) => {
+            s.parse::<f64>().ok().map(Value::from)
+        }
+        Some(_) => None,
+    }
+}
+
+fn string_list_arg(arguments: &Map<String, Value>, key: &str) -> Vec<String> {
+    arguments
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|i| i.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn usize_arg(arguments: &Map<String, Value>, key: &str) -> Option<usize> {
+    arguments
+        .get(key)
+        .and_then(|v| v.as_u64())
+        .map(|v| usize::try_from(v).unwrap_or(usize::MAX))
+}
+
+/// Whether a write to the table could currently succeed: editing is
+/// permitted, the file opens for writing (catching read-only files and
+/// mounts) and no other tool call holds it.
+fn table_writable(args: &Args, table_name: &str) -> bool {
+    if !args.permit_editing {
+        return false;
+    }
+    let Ok(path) = resolve_table_path(table_name, args) else {
+        return false;
+    };
+    std::fs::OpenOptions::new().write(true).open(&path).is_ok() && !locks::is_held(&path)
+}
+
+fn handle_list_tables(
+    args: &Args,
+    offset: usize,
+    limit: usize,
+    writable_only: bool,
+    detailed: bool,
+) -> Value {
+    let mut tables = sorted_table_names(&args.location);
+    if tables.is_empty() {
+        return json!({
+            "content": [{ "type": "text", "text": "No .db files found in location." }]
+        });
+    }
+    if writable_only {
+        tables.retain(|t| table_writable(args, t));
+        if tables.is_empty() {
+            return json!({
+                "content": [{ "type": "text", "text": "No writable tables found in location." }],
+                "_meta": { "total": 0, "tables": [] }
+            });
+        }
+    }
+
+    let total = tables.len();
+    let page: Vec<&str> = tables
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|t| t.as_str())
+        .collect();
+    let end = offset.saturating_add(page.len());
+    let mut text = if page.is_empty() {
+        format!("No tables at offset {} ({} tables total).", offset, total)
+    } else {
+        format!(
+            "Found tables ({}-{} of {}): {}",
+            offset + 1,
+            end,
+            total,
+            page.join(", ")
+        )
+    };
+
+    let mut ambiguous = Vec::new();
+    let annotated: Vec<Value> = page
+        .iter()
+        .map(|t| {
+            let paths = table_paths(args, t);
+            let mut entry =
+                json!({ "name": t, "writable": writable_only || table_writable(args, t) });
+            if let Some(path) = paths.first() {
+                entry["path"] = json!(path);
+            }
+            if paths.len() > 1 {
+                entry["alsoIn"] = json!(paths[1..]);
+                ambiguous.push(format!(
+                    "'{}' opens {} (also in {})",
+                    t,
+                    paths[0].display(),
+                    paths[1..]
+                        .iter()
+                        .map(|p| p.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            entry
+        })
+        .collect();
+    if !ambiguous.is_empty() {
+        text.push_str(&format!(
+            "\nIn several locations: {}.",
+            ambiguous.join("; ")
+        ));
+    }
+    let mut meta = json!({ "total": total, "tables": annotated });
+    if end < total {
+        meta["nextOffset"] = json!(end);
+    }
+    let mut result = if detailed {
+        let details: Vec<Value> = page.iter().map(|t| table_details(args, t)).collect();
+        data_response(args, text, &details)
+    } else {
+        json!({ "content": [{ "type": "text", "text": text }] })
+    };
+    result["_meta"] = meta;
+    result
+}
+
+/// A `list_tables` entry for `detailed: true`: the record count, which
+/// means opening the table, and the file's size and modification time.
+/// A table that can't be opened gets a null count and the reason.
+fn table_details(args: &Args, table_name: &str) -> Value {
+    let Some(path) = table_paths(args, table_name).into_iter().next() else {
+        return json!({ "name": table_name, "records": null, "error": "Table file not found." });
+    };
+    let metadata = std::fs::metadata(&path).ok();
+    let mut entry = json!({
+        "name": table_name,
+        "records": null,
+        "bytes": metadata.as_ref().map(|m| m.len()),
+        "modified": metadata
+            .and_then(|m| m.modified().ok())
+            .map(dates::format_system_time)
+    });
+    let records = locks::acquire(&path, args.lock_timeout_ms).and_then(|_lock| {
+        doccache::open(&path, args).map(|doc| unsafe { pxlib::PX_get_num_records(doc.as_ptr()) })
+    });
+    match records {
+        Ok(count) => entry["records"] = json!(count),
+        Err(e) => entry["error"] = json!(e.message),
+    }
+    entry
+}
+
+fn handle_count_records(table_name: &str, args: &Args) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let count = unsafe { pxlib::PX_get_num_records(doc.as_ptr()) };
+
+    data_response(
+        args,
+        format!("Table '{}' has {} records.", table_name, count),
+        &json!({ "count": count }),
+    )
+}
+
+/// Reports whether `table_name` resolves to an existing table file, and
+/// whether a `.mb` blob file sits beside it. Nothing is opened.
+fn handle_table_exists(table_name: &str, args: &Args) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    let exists = full_path.is_file();
+    let blob_file = exists
+        && ["mb", "MB"]
+            .iter()
+            .any(|ext| full_path.with_extension(ext).is_file());
+    let text = if exists {
+        format!("Table '{}' exists.", table_name)
+    } else {
+        format!("Table '{}' does not exist.", table_name)
+    };
+    data_response(
+        args,
+        text,
+        &json!({
+            "exists": exists,
+            "path": full_path.to_string_lossy(),
+            "blob_file": blob_file
+        }),
+    )
+}
+
+fn handle_get_table_info(table_name: &str, args: &Args) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let Some(db_info) = file_info(&full_path) else {
+        return ToolError::new(
+            "TABLE_NOT_FOUND",
+            format!("Table '{}' not found.", full_path.to_string_lossy()),
+        )
+        .into_value();
+    };
+    let mb_info = ["mb", "MB"]
+        .iter()
+        .find_map(|ext| file_info(&full_path.with_extension(ext)));
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    let info = unsafe {
+        // pxlib reports header values as floats; codepages are whole numbers.
+        let mut value: std::os::raw::c_float = 0.0;
+        let codepage = (pxlib::PX_get_value(pxdoc, c"codepage".as_ptr(), &mut value) == 0)
+            .then_some(value as i64);
+        json!({
+            "table": table_name,
+            "records": pxlib::PX_get_num_records(pxdoc),
+            "fields": pxlib::PX_get_num_fields(pxdoc),
+            "record_size": pxlib::PX_get_recordsize(pxdoc),
+            "layout_warning": doc.layout_warning(),
+            "codepage": codepage,
+            "db": db_info,
+            "mb": mb_info
+        })
+    };
+    json!({
+        "content": [
+            { "type": "text", "text": format!("Info for table '{}':", table_name) },
+            { "type": "text", "text": serde_json::to_string_pretty(&info).unwrap() }
+        ]
+    })
+}
+
+/// Filesystem provenance for a table file: size plus created/modified times
+/// (`created` is null where the filesystem doesn't record it).
+fn file_info(path: &Path) -> Option<Value> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    Some(json!({
+        "file": path.file_name().map(|n| n.to_string_lossy().into_owned()),
+        "bytes": metadata.len(),
+        "created": metadata.created().ok().map(dates::format_system_time),
+        "modified": metadata.modified().ok().map(dates::format_system_time)
+    }))
+}
+
+fn handle_read_schema(table_name: &str, args: &Args) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    let layout_warning = doc.layout_warning();
+
+    unsafe {
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let mut fields_info = Vec::new();
+
+        if !fields_ptr.is_null() {
+            let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+            for f in fields_slice {
+                if !f.px_fname.is_null() {
+                    let name = std::ffi::CStr::from_ptr(f.px_fname)
+                        .to_string_lossy()
+                        .into_owned();
+                    let ftype = f.px_ftype;
+                    let flen = f.px_flen;
+
+                    let type_str = field_type_name(ftype);
+
+                    let mut info = json!({
+                        "name": name,
+                        "type": type_str,
+                        "length": flen
+                    });
+                    // Only these types store a decimal count.
+                    if matches!(
+                        ftype as u32,
+                        pxlib::pxfNumber | pxlib::pxfCurrency | pxlib::pxfBCD
+                    ) {
+                        info["decimals"] = json!(f.px_fdc);
+                    }
+                    fields_info.push(info);
+                }
+            }
+        }
+
+        let mut summary = format!("Schema for table '{}':", table_name);
+        if let Some(warning) = layout_warning {
+            summary = format!("Warning: {}\n{}", warning, summary);
+        }
+        let mut response = data_response(args, summary, &fields_info);
+        if let Some(warning) = layout_warning {
+            response["_meta"] = json!({ "warnings": [warning] });
+        }
+        response
+    }
+}
+
+fn field_type_name(ftype: std::os::raw::c_char) -> &'static str {
+    match ftype as u32 {
+        pxlib::pxfAlpha => "ALPHA",
+        pxlib::pxfDate => "DATE",
+        pxlib::pxfShort => "SHORT",
+        pxlib::pxfLong => "LONG",
+        pxlib::pxfCurrency => "CURRENCY",
+        pxlib::pxfNumber => "NUMBER",
+        pxlib::pxfLogical => "LOGICAL",
+        pxlib::pxfMemoBLOb => "MEMO",
+        pxlib::pxfBLOb => "BLOB",
+        pxlib::pxfTime => "TIME",
+        pxlib::pxfTimestamp => "TIMESTAMP",
+        pxlib::pxfAutoInc => "AUTOINC",
+        pxlib::pxfBCD => "BCD",
+        pxlib::pxfBytes => "BYTES",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Decodes the one record at `idx`, with the same errors `update_record`
+/// gives for an index that is out of range or deleted. Serves both the
+/// `get_record` tool and `paradox://table/{name}/record/{index}`.
+fn handle_get_record(table_name: &str, args: &Args, idx: i32, opts: &ReadOptions) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+        let mut buf = vec![0u8; pxlib::PX_get_recordsize(pxdoc) as usize];
+        if let Err(e) = load_existing_record(pxdoc, idx, &mut buf) {
+            return e.into_value();
+        }
+        let mut record_map = decode_record(pxdoc, fields_slice, &mut buf);
+        opts.apply(&mut record_map, fields_slice);
+
+        metrics::add_rows_read(1);
+        let mut response = data_response(
+            args,
+            format!("Record {} of table '{}':", idx, table_name),
+            &Value::Object(record_map),
+        );
+        response["_meta"] = json!({
+            "index": idx,
+            "total": pxlib::PX_get_num_records(pxdoc)
+        });
+        response
+    }
+}
+
+fn handle_read_data(
+    table_name: &str,
+    args: &Args,
+    offset: i32,
+    limit: i32,
+    opts: &ReadOptions,
+    layout: &FieldLayout,
+) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    // A custom layout is the caller's answer to a size mismatch, so only
+    // the remaining layout errors stay fatal.
+    let opened = if layout.is_default() {
+        doccache::open(&full_path, args)
+    } else {
+        doccache::open_relaxed(&full_path, args)
+    };
+    let doc = match opened {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let offsets = match layout.offsets(fields_slice, record_size as usize) {
+            Ok(o) => o,
+            Err(e) => return e.into_value(),
+        };
+        let mut buf = vec![0u8; record_size as usize];
+        let mut results = Vec::new();
+
+        // An offset past the end is just an empty page.
+        let end = offset.saturating_add(limit).min(num_records);
+        for i in offset..end {
+            if !pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                let mut record_map = decode_record_at(pxdoc, fields_slice, &mut buf, &offsets);
+                opts.apply(&mut record_map, fields_slice);
+                results.push(Value::Object(record_map));
+            }
+        }
+        let count = (end - offset).max(0);
+
+        metrics::add_rows_read(results.len());
+        let mut summary = format!("Data for table '{}' ({} records):", table_name, count);
+        let next_offset = (offset + count < num_records).then_some(offset + count);
+        if let Some(next) = next_offset {
+            summary = format!(
+                "{}\n{} more records follow; pass offset {} for the next page.",
+                summary,
+                num_records - next,
+                next
+            );
+        }
+        let mut response = data_response(args, summary, &results);
+        response["_meta"] = json!({ "offset": offset, "total": num_records });
+        if let Some(next) = next_offset {
+            response["_meta"]["nextOffset"] = json!(next);
+        }
+        response
+    }
+}
+
+/// Returns a page of records in primary key order. The key fields are
+/// decoded and sorted here rather than read through the `.px` index:
+/// pxlib appends records where they land and its index only maps blocks,
+/// so attaching it doesn't put record numbers in key order.
+fn handle_read_sorted(
+    table_name: &str,
+    args: &Args,
+    offset: i32,
+    limit: i32,
+    opts: &ReadOptions,
+) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+        let key_count = primary_key_count(pxdoc).min(fields_slice.len());
+        let warning = (key_count == 0).then(|| {
+            "Table has no primary key; records are returned in physical order.".to_string()
+        });
+
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        let mut order = sorted_by_key(pxdoc, fields_slice, 0..key_count, &mut buf, |_| true);
+        let scanned = order.len();
+        order = order
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect();
+
+        let mut results = Vec::new();
+        for (_, i) in order {
+            if !pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                let mut record_map = decode_record(pxdoc, fields_slice, &mut buf);
+                opts.apply(&mut record_map, fields_slice);
+                results.push(Value::Object(record_map));
+            }
+        }
+
+        let mut summary = format!(
+            "Data for table '{}' in {} order ({} records):",
+            table_name,
+            if warning.is_some() { "physical" } else { "key" },
+            results.len()
+        );
+        if let Some(warning) = &warning {
+            log::warn!("{}: {}", table_name, warning);
+            summary = format!("Warning: {}\n{}", warning, summary);
+        }
+        metrics::add_rows_read(scanned);
+        let mut response = data_response(args, summary, &results);
+        if let Some(warning) = warning {
+            response["_meta"] = json!({ "warnings": [warning] });
+        }
+        response
+    }
+}
+
+/// The number of leading fields that form the table's primary key.
+unsafe fn primary_key_count(pxdoc: *mut pxlib::pxdoc_t) -> usize {
+    let head = (*pxdoc).px_head;
+    if head.is_null() {
+        return 0;
+    }
+    (*head).px_primarykeyfields.max(0) as usize
+}
+
+/// Scans the table, decoding only the `keys` range of `fields`, and returns
+/// the key and record number of every record whose key passes `keep`,
+/// sorted by key. The sort is stable, so equal keys stay in physical order.
+unsafe fn sorted_by_key(
+    pxdoc: *mut pxlib::pxdoc_t,
+    fields: &[pxlib::pxfield_t],
+    keys: std::ops::Range<usize>,
+    buf: &mut [u8],
+    keep: impl Fn(&[Value]) -> bool,
+) -> Vec<(Vec<Value>, i32)> {
+    let offsets = cumulative_offsets(fields);
+    let mut keyed = Vec::new();
+    for i in 0..pxlib::PX_get_num_records(pxdoc) {
+        if pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+            continue;
+        }
+        let key: Vec<Value> = fields[keys.clone()]
+            .iter()
+            .zip(&offsets[keys.clone()])
+            .map(|(f, &offset)| {
+                get_field_value(
+                    pxdoc,
+                    buf.as_mut_ptr().add(offset),
+                    f.px_ftype,
+                    f.px_flen,
+                    f.px_fdc,
+                )
+            })
+            .collect();
+        if keep(&key) {
+            keyed.push((key, i));
+        }
+    }
+    keyed.sort_by(|(a, _), (b, _)| compound_key_ordering(a, b));
+    keyed
+}
+
+/// Orders compound keys field by field, blanks first as Paradox does;
+/// values `key_ordering` can't compare count as equal.
+fn compound_key_ordering(a: &[Value], b: &[Value]) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    for (va, vb) in a.iter().zip(b) {
+        let ordering = match (va.is_null(), vb.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => key_ordering(va, vb).unwrap_or(Ordering::Equal),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Orders two key values: numbers numerically, strings lexicographically
+/// (ignoring ALPHA padding). Mixed or null keys have no order.
+fn key_ordering(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.trim_end().cmp(b.trim_end())),
+        _ => None,
+    }
+}
+
+fn handle_key_range(
+    table_name: &str,
+    args: &Args,
+    from_key: &Value,
+    to_key: &Value,
+    field: Option<&str>,
+    opts: &ReadOptions,
+) -> Value {
+    use std::cmp::Ordering;
+
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+        let field_name =
+            |f: &pxlib::pxfield_t| std::ffi::CStr::from_ptr(f.px_fname).to_string_lossy();
+
+        // Compound keys are ranged on their first field, as documented.
+        let index = match field {
+            None => 0,
+            Some(name) => match fields_slice.iter().position(|f| field_name(f) == name) {
+                Some(index) => index,
+                None => {
+                    return ToolError::new(
+                        "FIELD_NOT_FOUND",
+                        format!("Field '{}' not found in the table.", name),
+                    )
+                    .into_value();
+                }
+            },
+        };
+        let key_field = field_name(&fields_slice[index]).into_owned();
+        let warning = (primary_key_count(pxdoc) == 0).then(|| {
+            format!(
+                "Table has no primary key; all records were scanned and filtered on '{}'.",
+                key_field
+            )
+        });
+
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        let in_range = |key: &[Value]| {
+            key_ordering(&key[0], from_key).is_some_and(|o| o != Ordering::Less)
+                && key_ordering(&key[0], to_key).is_some_and(|o| o != Ordering::Greater)
+        };
+        let matches = sorted_by_key(pxdoc, fields_slice, index..index + 1, &mut buf, in_range);
+
+        let mut results = Vec::new();
+        for (_, i) in matches {
+            if !pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                let mut record_map = decode_record(pxdoc, fields_slice, &mut buf);
+                opts.apply(&mut record_map, fields_slice);
+                results.push(Value::Object(record_map));
+            }
+        }
+
+        let mut summary = format!(
+            "Records of table '{}' with {} from {} to {} ({} found):",
+            table_name,
+            key_field,
+            from_key,
+            to_key,
+            results.len()
+        );
+        if let Some(warning) = &warning {
+            log::warn!("{}: {}", table_name, warning);
+            summary = format!("Warning: {}\n{}", warning, summary);
+        }
+        metrics::add_rows_read(pxlib::PX_get_num_records(pxdoc).max(0) as usize);
+        let mut response = data_response(args, summary, &results);
+        if let Some(warning) = warning {
+            response["_meta"] = json!({ "warnings": [warning] });
+        }
+        response
+    }
+}
+
+fn handle_search_table(
+    table_name: &str,
+    args: &Args,
+    query: &Map<String, Value>,
+    case_sensitive: bool,
+    offset: usize,
+    limit: usize,
+    opts: &ReadOptions,
+) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    let matcher = match QueryMatcher::new([query], case_sensitive) {
+        Ok(m) => m,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        let offsets = cumulative_offsets(fields_slice);
+        let plan = matcher.plan(fields_slice, query);
+        let mut results = Vec::new();
+        let mut matched = 0;
+        let mut scanned = 0;
+        // Index of the first record left unexamined, when the scan stops early.
+        let mut truncated_at = None;
+        let limit_reached;
+
+        if args.search_threads > 1 {
+            // The whole range is scanned, then only the requested page decoded.
+            let end = args.max_scan_records.map_or(num_records, |max| {
+                num_records.min(i32::try_from(max).unwrap_or(i32::MAX))
+            });
+            let indices =
+                match parallel_matches(&full_path, args.search_threads, &matcher, query, end) {
+                    Ok(indices) => indices,
+                    Err(e) => return e.into_value(),
+                };
+            for &i in indices.iter().skip(offset).take(limit) {
+                if !pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                    let mut record_map = decode_record_at(pxdoc, fields_slice, &mut buf, &offsets);
+                    opts.apply(&mut record_map, fields_slice);
+                    results.push(Value::Object(record_map));
+                }
+            }
+            scanned = end.max(0) as usize;
+            truncated_at = (end < num_records).then_some(end);
+            limit_reached = indices.len() > offset.saturating_add(limit);
+        } else {
+            for i in 0..num_records {
+                if results.len() >= limit || args.max_scan_records.is_some_and(|max| scanned >= max)
+                {
+                    truncated_at = Some(i);
+                    break;
+                }
+                scanned += 1;
+                if pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null()
+                    || !matcher.matches_raw(pxdoc, fields_slice, &offsets, &mut buf, &plan)
+                {
+                    continue;
+                }
+                matched += 1;
+                if matched > offset {
+                    let mut record_map = decode_record_at(pxdoc, fields_slice, &mut buf, &offsets);
+                    opts.apply(&mut record_map, fields_slice);
+                    results.push(Value::Object(record_map));
+                }
+            }
+            limit_reached = results.len() >= limit && truncated_at.is_some();
+        }
+
+        metrics::add_rows_read(scanned);
+        let summary = match truncated_at {
+            Some(index) => format!(
+                "Search results for table '{}' ({} found; scan stopped at record {} of {}):",
+                table_name,
+                results.len(),
+                index,
+                num_records
+            ),
+            None => format!(
+                "Search results for table '{}' ({} found):",
+                table_name,
+                results.len()
+            ),
+        };
+        let mut response = data_response(args, summary, &results);
+        response["_meta"] = json!({
+            "scanned": scanned,
+            "complete": truncated_at.is_none(),
+            "truncatedAt": truncated_at,
+            "limitReached": limit_reached
+        });
+        if limit_reached {
+            response["_meta"]["nextOffset"] = json!(offset + results.len());
+        }
+        response
+    }
+}
+
+/// Indices of the records before `end` that match `query`, in ascending
+/// order. The range is split into one chunk per thread of the
+/// `--search-threads` pool; pxlib handles can't be shared between
+/// threads, so each chunk opens the table on its own.
+fn parallel_matches(
+    path: &Path,
+    threads: usize,
+    matcher: &QueryMatcher,
+    query: &Map<String, Value>,
+    end: std::os::raw::c_int,
+) -> Result<Vec<std::os::raw::c_int>, ToolError> {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    let pool = POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("search-{}", i))
+            .build()
+            .expect("failed to start the search thread pool")
+    });
+
+    let chunk = (end.max(0) as usize).div_ceil(threads).max(1) as std::os::raw::c_int;
+    let ranges: Vec<std::ops::Range<std::os::raw::c_int>> = (0..end)
+        .step_by(chunk as usize)
+        .map(|start| start..end.min(start.saturating_add(chunk)))
+        .collect();
+    let chunks: Vec<Result<Vec<std::os::raw::c_int>, ToolError>> = pool.install(|| {
+        ranges
+            .into_par_iter()
+            .map(|range| scan_matches(path, matcher, query, range))
+            .collect()
+    });
+    // `collect` keeps the chunks in range order, so the indices stay sorted.
+    let mut indices = Vec::new();
+    for chunk in chunks {
+        indices.extend(chunk?);
+    }
+    Ok(indices)
+}
+
+/// Scans `range` of the table at `path` on a handle of its own and returns
+/// the indices of the matching records.
+fn scan_matches(
+    path: &Path,
+    matcher: &QueryMatcher,
+    query: &Map<String, Value>,
+    range: std::ops::Range<std::os::raw::c_int>,
+) -> Result<Vec<std::os::raw::c_int>, ToolError> {
+    let mut doc = PxDoc::new()?;
+    // The caller's own handle has already applied `--strict-types`.
+    doc.open(path, false)?;
+    doc.attach_blob_file(path);
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+        let mut buf = vec![0u8; pxlib::PX_get_recordsize(pxdoc) as usize];
+        let offsets = cumulative_offsets(fields_slice);
+        let plan = matcher.plan(fields_slice, query);
+
+        let mut found = Vec::new();
+        for i in range {
+            if !pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null()
+                && matcher.matches_raw(pxdoc, fields_slice, &offsets, &mut buf, &plan)
+            {
+                found.push(i);
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Aggregate function accepted by `query_table`.
+#[derive(Clone, Copy, PartialEq)]
+enum AggregateOp {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateOp {
+    fn parse(op: &str) -> Option<Self> {
+        match op {
+            "count" => Some(AggregateOp::Count),
+            "sum" => Some(AggregateOp::Sum),
+            "avg" => Some(AggregateOp::Avg),
+            "min" => Some(AggregateOp::Min),
+            "max" => Some(AggregateOp::Max),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AggregateOp::Count => "count",
+            AggregateOp::Sum => "sum",
+            AggregateOp::Avg => "avg",
+            AggregateOp::Min => "min",
+            AggregateOp::Max => "max",
+        }
+    }
+}
+
+/// Running state of one aggregate; blank values are skipped.
+struct Aggregator {
+    op: AggregateOp,
+    field: Option<String>,
+    count: u64,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Aggregator {
+    fn new(op: AggregateOp, field: Option<String>) -> Self {
+        Aggregator {
+            op,
+            field,
+            count: 0,
+            sum: 0.0,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// The result key, e.g. `sum(Amount)` or `count`.
+    fn label(&self) -> String {
+        match &self.field {
+            Some(field) => format!("{}({})", self.op.name(), field),
+            None => self.op.name().to_string(),
+        }
+    }
+
+    fn add(&mut self, record: &Map<String, Value>) {
+        let Some(field) = &self.field else {
+            self.count += 1;
+            return;
+        };
+        if let Some(val) = record.get(field) {
+            self.add_value(val);
+        }
+    }
+
+    /// Accumulates a single value of the aggregated field.
+    fn add_value(&mut self, val: &Value) {
+        if val.is_null() {
+            return;
+        }
+        self.count += 1;
+        if let Some(v) = val.as_f64() {
+            self.sum += v;
+            self.min = Some(self.min.map_or(v, |m| m.min(v)));
+            self.max = Some(self.max.map_or(v, |m| m.max(v)));
+        }
+    }
+
+    fn result(&self) -> Value {
+        match self.op {
+            AggregateOp::Count => json!(self.count),
+            AggregateOp::Sum => json!(self.sum),
+            AggregateOp::Avg if self.count == 0 => Value::Null,
+            AggregateOp::Avg => json!(self.sum / self.count as f64),
+            AggregateOp::Min => json!(self.min),
+            AggregateOp::Max => json!(self.max),
+        }
+    }
+}
+
+fn is_numeric_field(ftype: std::os::raw::c_char) -> bool {
+    matches!(
+        ftype as u32,
+        pxlib::pxfShort
+            | pxlib::pxfLong
+            | pxlib::pxfAutoInc
+            | pxlib::pxfNumber
+            | pxlib::pxfCurrency
+            | pxlib::pxfBCD
+    )
+}
+
+/// The parsed arguments of a `query_table` call.
+struct QuerySpec {
+    filter: Map<String, Value>,
+    select: Vec<String>,
+    /// Sort keys as (field, descending).
+    order_by: Vec<(String, bool)>,
+    offset: usize,
+    limit: usize,
+    aggregates: Vec<(AggregateOp, Option<String>)>,
+}
+
+impl QuerySpec {
+    fn from_arguments(arguments: &Map<String, Value>) -> Result<Self, RpcError> {
+        let mut order_by = Vec::new();
+        for key in string_list_arg(arguments, "order_by") {
+            let mut parts = key.split_whitespace();
+            let field = parts.next().unwrap_or_default().to_string();
+            let desc = match parts.next().map(|d| d.to_ascii_lowercase()).as_deref() {
+                None | Some("asc") => false,
+                Some("desc") => true,
+                Some(_) => {
+                    return Err(RpcError::invalid_params(format!(
+                    "Invalid order_by entry '{}'; expected 'FIELD', 'FIELD asc' or 'FIELD desc'",
+                    key
+                )))
+                }
+            };
+            order_by.push((field, desc));
+        }
+
+        let mut aggregates = Vec::new();
+        if let Some(specs) = arguments.get("aggregate").and_then(|a| a.as_array()) {
+            for spec in specs {
+                let op = spec
+                    .get("op")
+                    .and_then(|o| o.as_str())
+                    .and_then(AggregateOp::parse)
+                    .ok_or_else(|| {
+                        RpcError::invalid_params(
+                            "Each aggregate needs an op of count, sum, avg, min or max",
+                        )
+                    })?;
+                let field = spec
+                    .get("field")
+                    .and_then(|f| f.as_str())
+                    .map(str::to_string);
+                if field.is_none() && op != AggregateOp::Count {
+                    return Err(RpcError::invalid_params(format!(
+                        "Aggregate '{}' needs a field",
+                        op.name()
+                    )));
+                }
+                aggregates.push((op, field));
+            }
+        }
+
+        Ok(QuerySpec {
+            filter: arguments
+                .get("filter")
+                .and_then(|f| f.as_object())
+                .cloned()
+                .unwrap_or_default(),
+            select: string_list_arg(arguments, "select"),
+            order_by,
+            offset: usize_arg(arguments, "offset").unwrap_or(0),
+            limit: usize_arg(arguments, "limit").unwrap_or(100),
+            aggregates,
+        })
+    }
+
+    /// Checks every referenced field against the schema, and that numeric
+    /// aggregates target numeric fields.
+    fn validate(&self, fields: &[pxlib::pxfield_t]) -> Result<(), ToolError> {
+        let ftype_of = |name: &str| {
+            fields
+                .iter()
+                .find(|f| unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy() == name)
+                .map(|f| f.px_ftype)
+        };
+        let referenced = self
+            .select
+            .iter()
+            .chain(self.order_by.iter().map(|(f, _)| f))
+            .chain(self.aggregates.iter().filter_map(|(_, f)| f.as_ref()));
+        for name in referenced {
+            if ftype_of(name).is_none() {
+                return Err(ToolError::new(
+                    "FIELD_NOT_FOUND",
+                    format!("Field '{}' not found in the table.", name),
+                ));
+            }
+        }
+        for (op, field) in &self.aggregates {
+            let Some(field) = field else { continue };
+            if *op != AggregateOp::Count && ftype_of(field).is_some_and(|t| !is_numeric_field(t)) {
+                return Err(ToolError::new(
+                    "AGGREGATE_TYPE_MISMATCH",
+                    format!(
+                        "Aggregate '{}' needs a numeric field; '{}' is not numeric.",
+                        op.name(),
+                        field
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares two records by the `order_by` keys, blanks last.
+    fn compare(&self, a: &Map<String, Value>, b: &Map<String, Value>) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+        for (field, desc) in &self.order_by {
+            let (va, vb) = (
+                a.get(field).unwrap_or(&Value::Null),
+                b.get(field).unwrap_or(&Value::Null),
+            );
+            let ordering = match (va.is_null(), vb.is_null()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => return Ordering::Greater,
+                (false, true) => return Ordering::Less,
+                (false, false) => key_ordering(va, vb).unwrap_or(Ordering::Equal),
+            };
+            let ordering = if *desc { ordering.reverse() } else { ordering };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+fn handle_query_table(
+    table_name: &str,
+    args: &Args,
+    spec: &QuerySpec,
+    opts: &ReadOptions,
+) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    let matcher = match QueryMatcher::new([&spec.filter], false) {
+        Ok(m) => m,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+
+        if let Err(e) = spec.validate(fields_slice) {
+            return e.into_value();
+        }
+
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        let mut aggregators: Vec<Aggregator> = spec
+            .aggregates
+            .iter()
+            .map(|(op, field)| Aggregator::new(*op, field.clone()))
+            .collect();
+        let streaming = spec.aggregates.is_empty() && spec.order_by.is_empty();
+        let mut matched = 0;
+        let mut scanned = 0;
+        let mut rows = Vec::new();
+
+        for i in 0..num_records {
+            if streaming && rows.len() >= spec.limit {
+                break;
+            }
+            if pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                continue;
+            }
+            scanned += 1;
+            let record_map = decode_record(pxdoc, fields_slice, &mut buf);
+            if !matcher.matches(&record_map, &spec.filter) {
+                continue;
+            }
+            matched += 1;
+            if !aggregators.is_empty() {
+                for agg in &mut aggregators {
+                    agg.add(&record_map);
+                }
+            } else if !streaming || matched > spec.offset {
+                rows.push(record_map);
+            }
+        }
+
+        if !spec.order_by.is_empty() {
+            rows.sort_by(|a, b| spec.compare(a, b));
+            rows = rows
+                .into_iter()
+                .skip(spec.offset)
+                .take(spec.limit)
+                .collect();
+        }
+
+        let results: Vec<Value> = rows
+            .into_iter()
+            .map(|mut record| {
+                opts.apply(&mut record, fields_slice);
+                if spec.select.is_empty() {
+                    Value::Object(record)
+                } else {
+                    Value::Object(
+                        spec.select
+                            .iter()
+                            .filter_map(|f| record.remove(f).map(|v| (f.clone(), v)))
+                            .collect(),
+                    )
+                }
+            })
+            .collect();
+
+        metrics::add_rows_read(scanned);
+
+        if !aggregators.is_empty() {
+            let result: Map<String, Value> = aggregators
+                .iter()
+                .map(|agg| (agg.label(), agg.result()))
+                .collect();
+            return data_response(
+                args,
+                format!(
+                    "Aggregates for table '{}' ({} matching records):",
+                    table_name, matched
+                ),
+                &result,
+            );
+        }
+        data_response(
+            args,
+            format!(
+                "Query results for table '{}' ({} records):",
+                table_name,
+                results.len()
+            ),
+            &results,
+        )
+    }
+}
+
+fn handle_multi_count(table_name: &str, args: &Args, queries: &Map<String, Value>) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    let matcher = match QueryMatcher::new(queries.values().filter_map(|q| q.as_object()), false) {
+        Ok(m) => m,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        let mut counts = vec![0u64; queries.len()];
+        let mut scanned = 0;
+
+        for i in 0..num_records {
+            if pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                continue;
+            }
+            scanned += 1;
+            let record_map = decode_record(pxdoc, fields_slice, &mut buf);
+            for (count, query) in counts.iter_mut().zip(queries.values()) {
+                if query
+                    .as_object()
+                    .is_some_and(|q| matcher.matches(&record_map, q))
+                {
+                    *count += 1;
+                }
+            }
+        }
+
+        metrics::add_rows_read(scanned);
+        let result: Map<String, Value> = queries
+            .keys()
+            .zip(counts)
+            .map(|(name, count)| (name.clone(), json!(count)))
+            .collect();
+        let mut response = data_response(
+            args,
+            format!(
+                "Match counts for table '{}' ({} records scanned):",
+                table_name, scanned
+            ),
+            &result,
+        );
+        response["_meta"] = json!({ "scanned": scanned });
+        response
+    }
+}
+
+fn handle_distinct_values(
+    table_name: &str,
+    args: &Args,
+    field_name: &str,
+    limit: Option<usize>,
+    opts: &ReadOptions,
+) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+
+        let Some(index) = fields_slice
+            .iter()
+            .position(|f| std::ffi::CStr::from_ptr(f.px_fname).to_string_lossy() == field_name)
+        else {
+            return ToolError::new(
+                "FIELD_NOT_FOUND",
+                format!("Field '{}' not found in the table.", field_name),
+            )
+            .into_value();
+        };
+        let field = &fields_slice[index];
+        let offset = cumulative_offsets(fields_slice)[index];
+
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        // Keyed by the value's JSON text, since `Value` isn't hashable.
+        let mut slots: HashMap<String, usize> = HashMap::new();
+        let mut values: Vec<(Value, u64)> = Vec::new();
+        let mut scanned = 0;
+
+        for i in 0..num_records {
+            if pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                continue;
+            }
+            scanned += 1;
+            let val = get_field_value(
+                pxdoc,
+                buf.as_mut_ptr().add(offset),
+                field.px_ftype,
+                field.px_flen,
+                field.px_fdc,
+            );
+            let slot = *slots.entry(val.to_string()).or_insert_with(|| {
+                values.push((val, 0));
+                values.len() - 1
+            });
+            values[slot].1 += 1;
+        }
+
+        metrics::add_rows_read(scanned);
+
+        values.sort_by(|(a, _), (b, _)| match (a.is_null(), b.is_null()) {
+            (true, true) => std::cmp::Ordering::Equal,
+            (true, false) => std::cmp::Ordering::Greater,
+            (false, true) => std::cmp::Ordering::Less,
+            (false, false) => {
+                key_ordering(a, b).unwrap_or_else(|| a.to_string().cmp(&b.to_string()))
+            }
+        });
+        let distinct = values.len();
+        // Rendering goes after sorting, so dates order by their ISO form
+        // whatever --date-format asks for.
+        let results: Vec<Value> = values
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(value, count)| {
+                let mut record = Map::new();
+                record.insert(field_name.to_string(), value);
+                opts.apply(&mut record, std::slice::from_ref(field));
+                let value = record.remove(field_name).unwrap_or(Value::Null);
+                json!({ "value": value, "count": count })
+            })
+            .collect();
+
+        let mut response = data_response(
+            args,
+            format!(
+                "Distinct values of '{}' in table '{}' ({} of {} shown):",
+                field_name,
+                table_name,
+                results.len(),
+                distinct
+            ),
+            &results,
+        );
+        response["_meta"] = json!({
+            "scanned": scanned,
+            "distinct": distinct,
+            "truncated": results.len() < distinct
+        });
+        response
+    }
+}
+
+fn handle_aggregate(
+    table_name: &str,
+    args: &Args,
+    field_name: &str,
+    op: AggregateOp,
+    query: &Map<String, Value>,
+) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    let matcher = match QueryMatcher::new([query], false) {
+        Ok(m) => m,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+
+        let Some(index) = fields_slice
+            .iter()
+            .position(|f| std::ffi::CStr::from_ptr(f.px_fname).to_string_lossy() == field_name)
+        else {
+            return ToolError::new(
+                "FIELD_NOT_FOUND",
+                format!("Field '{}' not found in the table.", field_name),
+            )
+            .into_value();
+        };
+        let field = &fields_slice[index];
+        if op != AggregateOp::Count && !is_numeric_field(field.px_ftype) {
+            return ToolError::new(
+                "AGGREGATE_TYPE_MISMATCH",
+                format!(
+                    "Aggregate '{}' needs a numeric field; '{}' is not numeric.",
+                    op.name(),
+                    field_name
+                ),
+            )
+            .into_value();
+        }
+
+        let offsets = cumulative_offsets(fields_slice);
+        let plan = matcher.plan(fields_slice, query);
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        let mut aggregator = Aggregator::new(op, Some(field_name.to_string()));
+        let mut matched = 0;
+        let mut scanned = 0;
+
+        for i in 0..num_records {
+            if pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                continue;
+            }
+            scanned += 1;
+            if !matcher.matches_raw(pxdoc, fields_slice, &offsets, &mut buf, &plan) {
+                continue;
+            }
+            matched += 1;
+            let val = get_field_value(
+                pxdoc,
+                buf.as_mut_ptr().add(offsets[index]),
+                field.px_ftype,
+                field.px_flen,
+                field.px_fdc,
+            );
+            aggregator.add_value(&val);
+        }
+
+        metrics::add_rows_read(scanned);
+
+        let result = aggregator.result();
+        let mut response = data_response(
+            args,
+            format!(
+                "{} for table '{}' ({} matching records):",
+                aggregator.label(),
+                table_name,
+                matched
+            ),
+            &result,
+        );
+        response["_meta"] = json!({ "scanned": scanned, "matched": matched });
+        response
+    }
+}
+
+/// Maps a `create_table` type name to its pxlib field type; unknown names
+/// fall back to ALPHA.
+fn field_type_from_name(name: &str) -> u32 {
+    match name.to_uppercase().as_str() {
+        "ALPHA" => pxlib::pxfAlpha,
+        "DATE" => pxlib::pxfDate,
+        "SHORT" => pxlib::pxfShort,
+        "LONG" => pxlib::pxfLong,
+        "CURRENCY" => pxlib::pxfCurrency,
+        "NUMBER" => pxlib::pxfNumber,
+        "LOGICAL" => pxlib::pxfLogical,
+        "MEMO" => pxlib::pxfMemoBLOb,
+        "BLOB" => pxlib::pxfBLOb,
+        "TIME" => pxlib::pxfTime,
+        "TIMESTAMP" => pxlib::pxfTimestamp,
+        "AUTOINC" => pxlib::pxfAutoInc,
+        "BCD" => pxlib::pxfBCD,
+        "BYTES" => pxlib::pxfBytes,
+        _ => pxlib::pxfAlpha,
+    }
+}
+
+/// Rejects ALPHA lengths outside `--min-alpha-length`..=`--max-alpha-length`
+/// before anything is allocated. A missing length counts as 0.
+fn check_alpha_lengths(fields: &[Value], args: &Args) -> Result<(), ToolError> {
+    let mut bad = Vec::new();
+    for f_val in fields {
+        let type_str = f_val
+            .get("type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("ALPHA");
+        if field_type_from_name(type_str) != pxlib::pxfAlpha {
+            continue;
+        }
+        let length = f_val.get("length").and_then(|v| v.as_i64()).unwrap_or(0);
+        if length < args.min_alpha_length as i64 || length > args.max_alpha_length as i64 {
+            let name = f_val
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("UNKNOWN");
+            bad.push(format!("{} ({})", name, length));
+        }
+    }
+    if bad.is_empty() {
+        return Ok(());
+    }
+    Err(ToolError::new(
+        "INVALID_FIELD_LENGTH",
+        format!(
+            "ALPHA field lengths must be between {} and {}; got {}.",
+            args.min_alpha_length,
+            args.max_alpha_length,
+            bad.join(", ")
+        ),
+    ))
+}
+
+/// Moves a table's `.db` and its `.mb`/`.px` companions aside to
+/// `<file>.<unix-seconds>.bak` so `create_table` can replace it. Returns the
+/// (original, backup) pairs for `restore_backups`.
+fn backup_table_files(db_path: &Path) -> Result<Vec<(PathBuf, PathBuf)>, ToolError> {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut moved = Vec::new();
+    for ext in ["db", "mb", "px", "DB", "MB", "PX"] {
+        let original = db_path.with_extension(ext);
+        if !original.exists()
+            || moved
+                .iter()
+                .any(|(o, _): &(PathBuf, PathBuf)| *o == original)
+        {
+            continue;
+        }
+        let mut backup = original.clone().into_os_string();
+        backup.push(format!(".{}.bak", stamp));
+        let backup = PathBuf::from(backup);
+        if let Err(e) = std::fs::rename(&original, &backup) {
+            restore_backups(&moved);
+            return Err(ToolError::new(
+                "BACKUP_FAILED",
+                format!("Failed to back up '{}': {}", original.display(), e),
+            ));
+        }
+        moved.push((original, backup));
+    }
+    Ok(moved)
+}
+
+fn restore_backups(moved: &[(PathBuf, PathBuf)]) {
+    for (original, backup) in moved {
+        if let Err(e) = std::fs::rename(backup, original) {
+            log::error!(
+                "Failed to restore '{}' from '{}': {}",
+                original.display(),
+                backup.display(),
+                e
+            );
+        }
+    }
+}
+
+fn handle_create_table(
+    table_name: &str,
+    args: &Args,
+    fields: &[Value],
+    overwrite: bool,
+    primary_keys: std::os::raw::c_int,
+) -> Value {
+    if let Err(e) = check_alpha_lengths(fields, args) {
+        return e.into_value();
+    }
+    if primary_keys as usize > fields.len() {
+        return ToolError::new(
+            "INVALID_PRIMARY_KEY",
+            format!(
+                "primary_key_fields is {}, but the table has only {} fields.",
+                primary_keys,
+                fields.len()
+            ),
+        )
+        .into_value();
+    }
+
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&full_path);
+
+    if full_path.exists() && !overwrite {
+        return table_exists_error(table_name).into_value();
+    }
+
+    let specs: Vec<FieldSpec> = fields.iter().map(FieldSpec::from_json).collect();
+    let backups = if full_path.exists() {
+        match backup_table_files(&full_path) {
+            Ok(moved) => moved,
+            Err(e) => return e.into_value(),
+        }
+    } else {
+        Vec::new()
+    };
+
+    match unsafe { create_table_file(&full_path, table_name, &specs, primary_keys) } {
+        Ok(()) => {
+            let text = format!(
+                "Successfully created table '{}' with {} fields.{}",
+                table_name,
+                fields.len(),
+                backups_note(&backups)
+            );
+            json!({ "content": [{ "type": "text", "text": text }] })
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&full_path);
+            let _ = std::fs::remove_file(full_path.with_extension("px"));
+            restore_backups(&backups);
+            e.into_value()
+        }
+    }
+}
+
+fn table_exists_error(table_name: &str) -> ToolError {
+    ToolError::new(
+        "TABLE_EXISTS",
+        format!(
+            "Table '{}' already exists; pass overwrite: true to replace it (the old files are kept as backups).",
+            table_name
+        ),
+    )
+}
+
+/// Tells the user where `backup_table_files` moved a replaced table.
+fn backups_note(backups: &[(PathBuf, PathBuf)]) -> String {
+    if backups.is_empty() {
+        return String::new();
+    }
+    let names: Vec<String> = backups
+        .iter()
+        .map(|(_, b)| b.display().to_string())
+        .collect();
+    format!(
+        " The previous table was backed up to: {}.",
+        names.join(", ")
+    )
+}
+
+/// One field of a table about to be created, as `PX_create_file` takes it.
+struct FieldSpec {
+    name: CString,
+    ftype: std::os::raw::c_char,
+    len: std::os::raw::c_int,
+    decimals: std::os::raw::c_int,
+}
+
+impl FieldSpec {
+    /// Reads a `create_table` field definition. Fixed-size types get their
+    /// length when none is given.
+    fn from_json(f_val: &Value) -> Self {
+        let name_str = f_val
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("UNKNOWN");
+        let type_str = f_val
+            .get("type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("ALPHA");
+        let length = f_val.get("length").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+        let decimals = f_val
+            .get("decimals")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0)
+            .clamp(0, 32) as i32;
+
+        let f_type = field_type_from_name(type_str);
+        let len = if length > 0 {
+            length
+        } else {
+            match f_type {
+                pxlib::pxfShort => 2,
+                pxlib::pxfLong | pxlib::pxfAutoInc | pxlib::pxfDate | pxlib::pxfTime => 4,
+                pxlib::pxfCurrency | pxlib::pxfNumber | pxlib::pxfTimestamp => 8,
+                pxlib::pxfLogical => 1,
+                pxlib::pxfBCD => 17,
+                _ => 0,
+            }
+        };
+        FieldSpec {
+            name: CString::new(name_str).unwrap_or_else(|_| CString::new("INVALID").unwrap()),
+            ftype: f_type as std::os::raw::c_char,
+            len,
+            decimals,
+        }
+    }
+
+    /// Copies the definition of an existing table's field.
+    unsafe fn from_field(field: &pxlib::pxfield_t) -> Self {
+        FieldSpec {
+            name: std::ffi::CStr::from_ptr(field.px_fname).to_owned(),
+            ftype: field.px_ftype,
+            len: field.px_flen,
+            decimals: field.px_fdc,
+        }
+    }
+}
+
+/// Creates an empty table file at `path` with `fields`, using file type 0
+/// (`pxfFileTypIndexDB`), and closes it so the header is flushed. With
+/// `primary_keys` above 0, the first that many fields form the primary key
+/// and its `.px` index is written too.
+unsafe fn create_table_file(
+    path: &Path,
+    table_name: &str,
+    fields: &[FieldSpec],
+    primary_keys: std::os::raw::c_int,
+) -> Result<(), ToolError> {
+    #[repr(C)]
+    struct PxField {
+        px_fname: *mut std::os::raw::c_char,
+        px_ftype: std::os::raw::c_char,
+        px_flen: std::os::raw::c_int,
+        px_fdc: std::os::raw::c_int,
+    }
+
+    extern "C" {
+        fn malloc(size: usize) -> *mut std::ffi::c_void;
+        fn strdup(s: *const std::os::raw::c_char) -> *mut std::os::raw::c_char;
+    }
+
+    let pxdoc = pxerror::new_doc();
+    if pxdoc.is_null() {
+        return Err(px_init_error());
+    }
+
+    let Ok(c_path) = CString::new(path.to_string_lossy().as_ref()) else {
+        pxlib::PX_delete(pxdoc);
+        return Err(invalid_path_error());
+    };
+
+    // pxlib adopts the field array and names on success and releases
+    // them with free() in PX_close, so they must come from malloc and
+    // strdup. Allocated last, once nothing else can fail before
+    // PX_create_file.
+    let fields_byte_size = std::mem::size_of::<PxField>() * fields.len();
+    let px_fields_ptr = malloc(fields_byte_size) as *mut PxField;
+    if px_fields_ptr.is_null() && fields_byte_size > 0 {
+        pxlib::PX_delete(pxdoc);
+        return Err(ToolError::new(
+            "OUT_OF_MEMORY",
+            "Failed to allocate the field array.",
+        ));
+    }
+
+    for (i, field) in fields.iter().enumerate() {
+        // Write into the malloc'd array directly to avoid double free
+        std::ptr::write(
+            px_fields_ptr.add(i),
+            PxField {
+                px_fname: strdup(field.name.as_ptr()),
+                px_ftype: field.ftype,
+                px_flen: field.len,
+                px_fdc: field.decimals,
+            },
+        );
+    }
+
+    let res = pxlib::PX_create_file(
+        pxdoc,
+        px_fields_ptr as *mut pxlib::pxfield_t,
+        fields.len() as i32,
+        c_path.as_ptr(),
+        0,
+    );
+
+    // When creation fails before pxlib takes the array (e.g. the file
+    // can't be opened), it is still ours to free.
+    if res < 0 && pxlib::PX_get_fields(pxdoc) != px_fields_ptr as *mut pxlib::pxfield_t {
+        for i in 0..fields.len() {
+            free((*px_fields_ptr.add(i)).px_fname as *mut std::ffi::c_void);
+        }
+        free(px_fields_ptr as *mut std::ffi::c_void);
+    }
+    let mut result = px_result(res, PxOp::Create, table_name);
+    if result.is_ok() && primary_keys > 0 {
+        result = write_primary_index(pxdoc, path, primary_keys);
+    }
+
+    // We MUST close the document to ensure the header and data are flushed.
+    // Closing also frees the field array pxlib adopted.
+    pxlib::PX_close(pxdoc);
+    pxlib::PX_delete(pxdoc);
+
+    result
+}
+
+/// Makes the first `key_fields` fields of the open table `pxdoc` its
+/// primary key and writes the `.px` index for its current records beside
+/// `table_path`.
+unsafe fn write_primary_index(
+    pxdoc: *mut pxlib::pxdoc_t,
+    table_path: &Path,
+    key_fields: std::os::raw::c_int,
+) -> Result<(), ToolError> {
+    let index_path = primary_index_path(table_path);
+    let index_name = index_path.to_string_lossy();
+    let c_index_path = CString::new(index_name.as_ref()).map_err(|_| invalid_path_error())?;
+    px_result(
+        pxlib::PX_set_value(
+            pxdoc,
+            c"numprimkeys".as_ptr(),
+            key_fields as std::os::raw::c_float,
+        ),
+        PxOp::Create,
+        &index_name,
+    )?;
+
+    let pindex = pxerror::new_doc();
+    if pindex.is_null() {
+        return Err(px_init_error());
+    }
+    let mut res = pxlib::PX_create_file(
+        pindex,
+        std::ptr::null_mut(),
+        0,
+        c_index_path.as_ptr(),
+        pxlib::pxfFileTypPrimIndex as std::os::raw::c_int,
+    );
+    if res >= 0 {
+        res = pxlib::PX_write_primary_index(pxdoc, pindex);
+    }
+    pxlib::PX_close(pindex);
+    pxlib::PX_delete(pindex);
+    if res < 0 {
+        let _ = std::fs::remove_file(&index_path);
+    }
+    px_result(res, PxOp::Create, &index_name)
+}
+
+/// Rewrites the primary index of a keyed table after its records changed,
+/// so it never describes an older state of the table. If that fails the
+/// index is removed, leaving readers to fall back to a table scan rather
+/// than trust a stale one. Tables without a primary key are left alone.
+fn refresh_primary_index(table_path: &Path) {
+    let refreshed = (|| {
+        let mut doc = PxDoc::new()?;
+        doc.open(table_path, false)?;
+        unsafe {
+            let head = (*doc.as_ptr()).px_head;
+            if head.is_null() || (*head).px_primarykeyfields <= 0 {
+                return Ok(());
+            }
+            write_primary_index(doc.as_ptr(), table_path, (*head).px_primarykeyfields)
+        }
+    })();
+    if let Err(e) = refreshed {
+        log::warn!(
+            "Failed to rebuild the primary index of {}: {}",
+            table_path.display(),
+            e.message
+        );
+        let _ = std::fs::remove_file(primary_index_path(table_path));
+    }
+}
+
+/// The table's `.px` file, in whichever case exists, else lowercase.
+fn primary_index_path(table_path: &Path) -> PathBuf {
+    ["px", "PX"]
+        .iter()
+        .map(|ext| table_path.with_extension(ext))
+        .find(|p| p.exists())
+        .unwrap_or_else(|| table_path.with_extension("px"))
+}
+
+/// Copies `source` to `destination`: a new table with the same fields, a
+/// copy of the source's `.mb` blob file, and every record byte for byte,
+/// so MEMO and BLOB fields keep pointing at the same blob data.
+fn handle_copy_table(source: &str, destination: &str, args: &Args, overwrite: bool) -> Value {
+    let src_path = match resolve_table_path(source, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    let dst_path = match resolve_table_path(destination, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    if !src_path.is_file() {
+        return ToolError::new("TABLE_NOT_FOUND", format!("Table '{}' not found.", source))
+            .into_value();
+    }
+    if src_path == dst_path {
+        return ToolError::new(
+            "SAME_TABLE",
+            format!("'{}' and '{}' are the same table.", source, destination),
+        )
+        .into_value();
+    }
+
+    let _src_lock = match locks::acquire(&src_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    let _dst_lock = match locks::acquire(&dst_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&dst_path);
+
+    if dst_path.exists() && !overwrite {
+        return table_exists_error(destination).into_value();
+    }
+
+    let mut src = match PxDoc::new() {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    if let Err(e) = src.open(&src_path, args.strict_types) {
+        return e.into_value();
+    }
+    let src_ptr = src.as_ptr();
+    let (specs, autoinc, primary_keys) = unsafe {
+        let num_fields = pxlib::PX_get_num_fields(src_ptr);
+        let fields_ptr = pxlib::PX_get_fields(src_ptr);
+        let specs: Vec<FieldSpec> = std::slice::from_raw_parts(fields_ptr, num_fields as usize)
+            .iter()
+            .map(|f| FieldSpec::from_field(f))
+            .collect();
+        let mut counter: std::os::raw::c_float = 0.0;
+        let autoinc = (pxlib::PX_get_value(src_ptr, c"autoinc".as_ptr(), &mut counter) == 0)
+            .then_some(counter);
+        let head = (*src_ptr).px_head;
+        let primary_keys = if head.is_null() {
+            0
+        } else {
+            (*head).px_primarykeyfields
+        };
+        (specs, autoinc, primary_keys)
+    };
+
+    let backups = if dst_path.exists() {
+        match backup_table_files(&dst_path) {
+            Ok(moved) => moved,
+            Err(e) => return e.into_value(),
+        }
+    } else {
+        Vec::new()
+    };
+    let discard = |e: ToolError| {
+        for ext in ["db", "mb", "px"] {
+            let _ = std::fs::remove_file(dst_path.with_extension(ext));
+        }
+        restore_backups(&backups);
+        e.into_value()
+    };
+
+    // The key is set once the records are in, so the index covers them.
+    if let Err(e) = unsafe { create_table_file(&dst_path, destination, &specs, 0) } {
+        return discard(e);
+    }
+    if let Some(blob_path) = ["mb", "MB"]
+        .iter()
+        .map(|ext| src_path.with_extension(ext))
+        .find(|p| p.is_file())
+    {
+        if let Err(e) = std::fs::copy(&blob_path, dst_path.with_extension("mb")) {
+            return discard(ToolError::new(
+                "COPY_FAILED",
+                format!("Failed to copy '{}': {}", blob_path.display(), e),
+            ));
+        }
+    }
+
+    let mut dst = match PxDoc::new() {
+        Ok(doc) => doc,
+        Err(e) => return discard(e),
+    };
+    if let Err(e) = dst.open(&dst_path, false) {
+        return discard(e);
+    }
+    let dst_ptr = dst.as_ptr();
+
+    let copied = unsafe {
+        let num_records = pxlib::PX_get_num_records(src_ptr);
+        let mut buf = vec![0u8; pxlib::PX_get_recordsize(src_ptr) as usize];
+        let mut copied = 0;
+        let mut failure = None;
+        for idx in 0..num_records {
+            if pxlib::PX_get_record(src_ptr, idx, buf.as_mut_ptr()).is_null() {
+                failure = Some(ToolError::new(
+                    "COPY_FAILED",
+                    format!("Failed to read record {} of '{}'.", idx, source),
+                ));
+                break;
+            }
+            let res = pxlib::PX_put_record(dst_ptr, buf.as_mut_ptr() as *mut std::os::raw::c_char);
+            if let Err(e) = px_result(res, PxOp::Write, destination) {
+                failure = Some(e);
+                break;
+            }
+            copied += 1;
+        }
+        if let (Some(counter), None) = (autoinc, &failure) {
+            if pxlib::PX_set_value(dst_ptr, c"autoinc".as_ptr(), counter) < 0 {
+                log::debug!(
+                    "Could not copy the AUTOINC counter: {}",
+                    pxerror::take_error().unwrap_or_default()
+                );
+            }
+        }
+        if let (true, None) = (primary_keys > 0, &failure) {
+            failure = write_primary_index(dst_ptr, &dst_path, primary_keys).err();
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(copied),
+        }
+    };
+
+    // Closing flushes the copy, so it must happen before reporting.
+    drop(dst);
+    drop(src);
+
+    match copied {
+        Ok(copied) => {
+            metrics::add_rows_written(copied);
+            json!({
+                "content": [{ "type": "text", "text": format!(
+                    "Successfully copied table '{}' to '{}' ({} records).{}",
+                    source, destination, copied, backups_note(&backups)
+                ) }],
+                "_meta": { "records": copied }
+            })
+        }
+        Err(e) => discard(e),
+    }
+}
+
+fn handle_write_record(
+    table_name: &str,
+    args: &Args,
+    index: Option<i32>,
+    record_data: &Map<String, Value>,
+) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let (record_data, ignored) = match protect_readonly_fields(args, table_name, record_data) {
+        Ok(r) => r,
+        Err(e) => return e.into_value(),
+    };
+    let record_data = &record_data;
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&full_path);
+
+    let mut doc = match PxDoc::new() {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    if let Err(e) = doc.open(&full_path, args.strict_types) {
+        return e.into_value();
+    }
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+
+        if let Some(idx) = index {
+            if let Err(e) = load_existing_record(pxdoc, idx, &mut buf) {
+                return e.into_value();
+            }
+        }
+
+        let warnings =
+            fill_record_buffer(pxdoc, fields_slice, &mut buf, record_data, args.date_format);
+
+        let mut autoinc_values = Map::new();
+        let res = if let Some(idx) = index {
+            pxlib::PX_put_recordn(pxdoc, buf.as_mut_ptr() as *mut std::os::raw::c_char, idx)
+        } else {
+            let mut autoinc = AutoInc::new(pxdoc, fields_slice);
+            if let Some(autoinc) = &autoinc {
+                autoinc.fill(pxdoc, &mut buf);
+            }
+            let new_index = pxlib::PX_get_num_records(pxdoc);
+            let res = pxlib::PX_put_record(pxdoc, buf.as_mut_ptr() as *mut std::os::raw::c_char);
+            if let (Some(autoinc), true) = (&mut autoinc, res >= 0) {
+                autoinc.advance();
+                autoinc.save(pxdoc);
+                autoinc_values = read_autoinc_values(pxdoc, fields_slice, &mut buf, new_index);
+            }
+            res
+        };
+
+        // Closing flushes the write, so it must happen before reporting.
+        drop(doc);
+        if res >= 0 {
+            refresh_primary_index(&full_path);
+        }
+
+        match px_result(res, PxOp::Write, table_name) {
+            Ok(()) => {
+                metrics::add_rows_written(1);
+                let mut text = format!(
+                    "Successfully {} record in table '{}'.",
+                    if index.is_some() {
+                        "updated"
+                    } else {
+                        "inserted"
+                    },
+                    table_name
+                );
+                if !ignored.is_empty() {
+                    text.push_str(&format!(
+                        " Ignored write-protected fields: {}.",
+                        ignored.join(", ")
+                    ));
+                }
+                let mut meta = Map::new();
+                if !autoinc_values.is_empty() {
+                    let assigned: Vec<String> = autoinc_values
+                        .iter()
+                        .map(|(field, value)| format!("{} = {}", field, value))
+                        .collect();
+                    text.push_str(&format!(" Assigned {}.", assigned.join(", ")));
+                    meta.insert("autoinc_values".to_string(), json!(autoinc_values));
+                }
+                for warning in &warnings {
+                    text.push_str(&format!(" Warning: {}", warning));
+                }
+                if !warnings.is_empty() {
+                    meta.insert("warnings".to_string(), json!(warnings));
+                }
+                if meta.is_empty() {
+                    return json!({ "content": [{ "type": "text", "text": text }] });
+                }
+                json!({
+                    "content": [{ "type": "text", "text": text }],
+                    "_meta": meta
+                })
+            }
+            Err(e) => e.into_value(),
+        }
+    }
+}
+
+fn handle_delete_record(table_name: &str, args: &Args, index: i32) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&full_path);
+
+    let mut doc = match PxDoc::new() {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    if let Err(e) = doc.open(&full_path, args.strict_types) {
+        return e.into_value();
+    }
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        if index >= num_records {
+            return ToolError::new(
+                "INDEX_OUT_OF_RANGE",
+                format!(
+                    "Record index {} does not exist (table has {} records).",
+                    index, num_records
+                ),
+            )
+            .into_value();
+        }
+
+        let res = pxlib::PX_delete_record(pxdoc, index);
+
+        // Closing flushes the change, so it must happen before reporting.
+        drop(doc);
+        if res >= 0 {
+            refresh_primary_index(&full_path);
+        }
+
+        match px_result(res, PxOp::Write, table_name) {
+            Ok(()) => {
+                metrics::add_rows_written(1);
+                json!({ "content": [{ "type": "text", "text": format!(
+                    "Successfully deleted record {} from table '{}'. Later records move down by one index.",
+                    index, table_name
+                ) }] })
+            }
+            Err(e) => e.into_value(),
+        }
+    }
+}
+
+fn handle_truncate_table(table_name: &str, args: &Args) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&full_path);
+
+    let mut doc = match PxDoc::new() {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    if let Err(e) = doc.open(&full_path, args.strict_types) {
+        return e.into_value();
+    }
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        // Deleting from the end means no remaining record has to move.
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let mut removed = 0;
+        let mut failure = None;
+        for idx in (0..num_records).rev() {
+            if let Err(e) = px_result(pxlib::PX_delete_record(pxdoc, idx), PxOp::Write, table_name)
+            {
+                failure = Some(e);
+                break;
+            }
+            removed += 1;
         }
-        _ => json!({}),
-    }
-}
 
-fn handle_list_tables(args: &Args) -> Value {
-    let mut tables = Vec::new();
-    if let Ok(entries) = std::fs::read_dir(&args.location) {
-        for res in entries {
-            if let Ok(entry) = res {
-                if entry.path().extension().and_then(|o| o.to_str()) == Some("db") {
-                    if let Some(name) = entry.path().file_name().and_then(|n| n.to_str()) {
-                        tables.push(name.to_string());
-                    }
-                }
-            }
+        // Closing flushes the changes, so it must happen before reporting.
+        drop(doc);
+        if removed > 0 {
+            refresh_primary_index(&full_path);
+        }
+        metrics::add_rows_written(removed);
+
+        if let Some(e) = failure {
+            let mut response = e.into_value();
+            response["_meta"]["removed"] = json!(removed);
+            return response;
         }
-    }
-    if tables.is_empty() {
-        json!({
-            "content": [{ "type": "text", "text": "No .db files found in location." }]
-        })
-    } else {
         json!({
-            "content": [{ "type": "text", "text": format!("Found tables: {}", tables.join(", ")) }]
+            "content": [{ "type": "text", "text": format!(
+                "Successfully removed {} records from table '{}'.",
+                removed, table_name
+            ) }],
+            "_meta": { "removed": removed }
         })
     }
 }
 
-fn handle_read_schema(table_name: &str, location: &str) -> Value {
-    let mut full_path = Path::new(location).join(table_name);
-    if full_path.extension().is_none() {
-        full_path.set_extension("db");
+/// Deletes a table's `.db` file and every companion `table_files` finds.
+/// Companions that don't exist are simply not listed.
+fn handle_drop_table(table_name: &str, args: &Args) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    if !full_path.is_file() {
+        return ToolError::new(
+            "TABLE_NOT_FOUND",
+            format!("Table '{}' not found.", table_name),
+        )
+        .into_value();
     }
 
-    let path_str = full_path.to_string_lossy();
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&full_path);
 
-    unsafe {
-        let pxdoc = pxlib::PX_new();
-        if pxdoc.is_null() {
-            return json!({ "isError": true, "content": [{ "type": "text", "text": "Failed to initialize PX library." }] });
+    let mut removed = Vec::new();
+    for path in table_files(&full_path) {
+        if let Err(e) = std::fs::remove_file(&path) {
+            let mut response = ToolError::new(
+                "DELETE_FAILED",
+                format!("Failed to delete '{}': {}", path.display(), e),
+            )
+            .into_value();
+            response["_meta"]["removed"] = json!(removed);
+            return response;
         }
+        removed.push(path.display().to_string());
+    }
+    json!({
+        "content": [{ "type": "text", "text": format!(
+            "Successfully dropped table '{}'. Removed: {}.",
+            table_name,
+            removed.join(", ")
+        ) }],
+        "_meta": { "removed": removed }
+    })
+}
 
-        let c_path = match CString::new(path_str.as_ref()) {
-            Ok(c) => c,
-            Err(_) => {
-                pxlib::PX_delete(pxdoc);
-                return json!({ "isError": true, "content": [{ "type": "text", "text": "Invalid table path string." }] });
-            }
-        };
+/// A table's `.db` file followed by the companions Paradox keeps beside it
+/// under the same name: the primary index (`.px`), blob file (`.mb`),
+/// validity checks (`.val`) and secondary indexes (`.xg*`, `.yg*`), with
+/// extensions in any letter case.
+fn table_files(db_path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (db_path.parent(), db_path.file_stem()) else {
+        return vec![db_path.to_path_buf()];
+    };
+    let mut companions: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path != db_path && path.file_stem() == Some(stem) && path.is_file())
+        .filter(|path| {
+            let ext = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            matches!(ext.as_str(), "px" | "mb" | "val")
+                || ext.starts_with("xg")
+                || ext.starts_with("yg")
+        })
+        .collect();
+    companions.sort();
+    companions.insert(0, db_path.to_path_buf());
+    companions
+}
 
-        if pxlib::PX_open_file(pxdoc, c_path.as_ptr()) < 0 {
-            pxlib::PX_delete(pxdoc);
-            return json!({ "isError": true, "content": [{ "type": "text", "text": format!("Failed to open table '{}'", path_str) }] });
-        }
+/// Updates every record matching `query` with `patch`, or deletes them
+/// when there is no patch. Matches are collected first and changed from
+/// the highest index down, so deletions never shift a pending index.
+fn handle_modify_by_query(
+    table_name: &str,
+    args: &Args,
+    query: &Map<String, Value>,
+    patch: Option<&Map<String, Value>>,
+    max_affected: usize,
+) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    let matcher = match QueryMatcher::new([query], false) {
+        Ok(m) => m,
+        Err(e) => return e.into_value(),
+    };
+    let (patch, ignored) = match patch.map(|p| protect_readonly_fields(args, table_name, p)) {
+        Some(Ok((patch, ignored))) => (Some(patch), ignored),
+        Some(Err(e)) => return e.into_value(),
+        None => (None, Vec::new()),
+    };
 
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&full_path);
+
+    let mut doc = match PxDoc::new() {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    if let Err(e) = doc.open(&full_path, args.strict_types) {
+        return e.into_value();
+    }
+    doc.attach_blob_file(&full_path);
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
         let num_fields = pxlib::PX_get_num_fields(pxdoc);
         let fields_ptr = pxlib::PX_get_fields(pxdoc);
-        let mut fields_info = Vec::new();
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        let offsets = cumulative_offsets(fields_slice);
+        let plan = matcher.plan(fields_slice, query);
 
-        if !fields_ptr.is_null() {
-            let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
-            for f in fields_slice {
-                if !f.px_fname.is_null() {
-                    let name = std::ffi::CStr::from_ptr(f.px_fname)
-                        .to_string_lossy()
-                        .into_owned();
-                    let ftype = f.px_ftype;
-                    let flen = f.px_flen;
+        let mut indices = Vec::new();
+        for i in 0..num_records {
+            if !pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null()
+                && matcher.matches_raw(pxdoc, fields_slice, &offsets, &mut buf, &plan)
+            {
+                indices.push(i);
+            }
+        }
+        metrics::add_rows_read(num_records.max(0) as usize);
+        if indices.len() > max_affected {
+            return ToolError::new(
+                "TOO_MANY_MATCHES",
+                format!(
+                    "{} records match, more than max_affected ({}); nothing was changed.",
+                    indices.len(),
+                    max_affected
+                ),
+            )
+            .into_value();
+        }
 
-                    let type_str = match ftype as u32 {
-                        pxlib::pxfAlpha => "ALPHA",
-                        pxlib::pxfDate => "DATE",
-                        pxlib::pxfShort => "SHORT",
-                        pxlib::pxfLong => "LONG",
-                        pxlib::pxfCurrency => "CURRENCY",
-                        pxlib::pxfNumber => "NUMBER",
-                        pxlib::pxfLogical => "LOGICAL",
-                        pxlib::pxfMemoBLOb => "MEMO",
-                        pxlib::pxfBLOb => "BLOB",
-                        pxlib::pxfTime => "TIME",
-                        pxlib::pxfTimestamp => "TIMESTAMP",
-                        pxlib::pxfAutoInc => "AUTOINC",
-                        pxlib::pxfBCD => "BCD",
-                        pxlib::pxfBytes => "BYTES",
-                        _ => "UNKNOWN",
-                    };
-
-                    fields_info.push(json!({
-                        "name": name,
-                        "type": type_str,
-                        "length": flen
-                    }));
+        let verb = if patch.is_some() {
+            "updated"
+        } else {
+            "deleted"
+        };
+        let mut warnings = Vec::new();
+        let mut affected = Vec::new();
+        let mut failure = None;
+        for &idx in indices.iter().rev() {
+            let res = match &patch {
+                Some(patch) => {
+                    if pxlib::PX_get_record(pxdoc, idx, buf.as_mut_ptr()).is_null() {
+                        -1
+                    } else {
+                        // Every record gets the same patch, so the same warnings.
+                        warnings = fill_record_buffer(
+                            pxdoc,
+                            fields_slice,
+                            &mut buf,
+                            patch,
+                            args.date_format,
+                        );
+                        pxlib::PX_put_recordn(
+                            pxdoc,
+                            buf.as_mut_ptr() as *mut std::os::raw::c_char,
+                            idx,
+                        )
+                    }
                 }
+                None => pxlib::PX_delete_record(pxdoc, idx),
+            };
+            if let Err(e) = px_result(res, PxOp::Write, table_name) {
+                failure = Some(e);
+                break;
             }
+            affected.push(idx);
         }
 
-        pxlib::PX_close(pxdoc);
-        pxlib::PX_delete(pxdoc);
+        // Closing flushes the changes, so it must happen before reporting.
+        drop(doc);
+        if !affected.is_empty() {
+            refresh_primary_index(&full_path);
+        }
+        metrics::add_rows_written(affected.len());
 
-        json!({
-            "content": [
-                { "type": "text", "text": format!("Schema for table '{}':", table_name) },
-                { "type": "text", "text": serde_json::to_string_pretty(&fields_info).unwrap() }
-            ]
-        })
+        if let Some(e) = failure {
+            let mut response = e.into_value();
+            response["_meta"]["affected"] = json!(affected);
+            return response;
+        }
+        let mut text = format!(
+            "Successfully {} {} records in table '{}'.",
+            verb,
+            affected.len(),
+            table_name
+        );
+        if !ignored.is_empty() {
+            text.push_str(&format!(
+                " Ignored write-protected fields: {}.",
+                ignored.join(", ")
+            ));
+        }
+        for warning in &warnings {
+            text.push_str(&format!(" Warning: {}", warning));
+        }
+        affected.reverse();
+        let mut response = json!({
+            "content": [{ "type": "text", "text": text }],
+            "_meta": { "affected": affected.len(), "indices": affected }
+        });
+        if !warnings.is_empty() {
+            response["_meta"]["warnings"] = json!(warnings);
+        }
+        response
     }
 }
 
-fn handle_read_data(table_name: &str, location: &str, limit: i32) -> Value {
-    let mut full_path = Path::new(location).join(table_name);
-    if full_path.extension().is_none() {
-        full_path.set_extension("db");
-    }
-
-    let path_str = full_path.to_string_lossy();
-
-    unsafe {
-        let pxdoc = pxlib::PX_new();
-        if pxdoc.is_null() {
-            return json!({ "isError": true, "content": [{ "type": "text", "text": "Failed to initialize PX library." }] });
-        }
+fn handle_bulk_insert(table_name: &str, args: &Args, records: &[Value]) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
 
-        let c_path = match CString::new(path_str.as_ref()) {
-            Ok(c) => c,
-            Err(_) => {
-                pxlib::PX_delete(pxdoc);
-                return json!({ "isError": true, "content": [{ "type": "text", "text": "Invalid table path string." }] });
-            }
-        };
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&full_path);
 
-        if pxlib::PX_open_file(pxdoc, c_path.as_ptr()) < 0 {
-            pxlib::PX_delete(pxdoc);
-            return json!({ "isError": true, "content": [{ "type": "text", "text": format!("Failed to open table '{}'", path_str) }] });
-        }
+    let mut doc = match PxDoc::new() {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    if let Err(e) = doc.open(&full_path, args.strict_types) {
+        return e.into_value();
+    }
+    let pxdoc = doc.as_ptr();
 
-        let num_records = pxlib::PX_get_num_records(pxdoc);
+    unsafe {
         let num_fields = pxlib::PX_get_num_fields(pxdoc);
         let fields_ptr = pxlib::PX_get_fields(pxdoc);
         let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
-
         let record_size = pxlib::PX_get_recordsize(pxdoc);
         let mut buf = vec![0u8; record_size as usize];
-        let mut results = Vec::new();
 
-        let count = if num_records < limit {
-            num_records
-        } else {
-            limit
-        };
+        let mut autoinc = AutoInc::new(pxdoc, fields_slice);
+        let mut results = Vec::new();
+        let mut inserted = 0;
+        for (i, record) in records.iter().enumerate() {
+            let Some(record_data) = record.as_object() else {
+                results.push(json!({ "record": i, "error": "Record is not an object" }));
+                continue;
+            };
+            let (record_data, ignored) =
+                match protect_readonly_fields(args, table_name, record_data) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        results.push(json!({ "record": i, "error": e.message }));
+                        continue;
+                    }
+                };
+            let record_data = &record_data;
 
-        for i in 0..count {
-            if !pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
-                let mut record_map = Map::new();
-                let mut offset = 0;
-                for f_idx in 0..num_fields {
-                    let f = &fields_slice[f_idx as usize];
-                    let field_name = std::ffi::CStr::from_ptr(f.px_fname)
-                        .to_string_lossy()
-                        .into_owned();
-                    let field_type = f.px_ftype;
-                    let field_len = f.px_flen;
+            buf.fill(0);
+            let warnings =
+                fill_record_buffer(pxdoc, fields_slice, &mut buf, record_data, args.date_format);
+            if let Some(autoinc) = &autoinc {
+                autoinc.fill(pxdoc, &mut buf);
+            }
 
-                    let val =
-                        get_field_value(pxdoc, buf.as_mut_ptr().add(offset), field_type, field_len);
-                    record_map.insert(field_name, val);
+            // Records are appended, so the new row lands at the current count.
+            let index = pxlib::PX_get_num_records(pxdoc);
+            let res = pxlib::PX_put_record(pxdoc, buf.as_mut_ptr() as *mut std::os::raw::c_char);
+            if let Err(e) = px_result(res, PxOp::Write, table_name) {
+                results.push(json!({ "record": i, "error": e.message }));
+                continue;
+            }
+            inserted += 1;
+            if let Some(autoinc) = &mut autoinc {
+                autoinc.advance();
+            }
 
-                    offset += field_len as usize;
-                }
-                results.push(Value::Object(record_map));
+            // Read the row back so the assigned AUTOINC values are reported.
+            let autoinc_values = read_autoinc_values(pxdoc, fields_slice, &mut buf, index);
+            let mut entry =
+                json!({ "record": i, "index": index, "autoinc_values": autoinc_values });
+            if !ignored.is_empty() {
+                entry["ignored_fields"] = json!(ignored);
             }
+            if !warnings.is_empty() {
+                entry["warnings"] = json!(warnings);
+            }
+            results.push(entry);
+        }
+        if let (Some(autoinc), true) = (&autoinc, inserted > 0) {
+            autoinc.save(pxdoc);
         }
 
-        pxlib::PX_close(pxdoc);
-        pxlib::PX_delete(pxdoc);
+        drop(doc);
+        if inserted > 0 {
+            refresh_primary_index(&full_path);
+        }
 
-        json!({
-            "content": [
-                { "type": "text", "text": format!("Data for table '{}' ({} records):", table_name, count) },
-                { "type": "text", "text": serde_json::to_string_pretty(&results).unwrap() }
-            ]
-        })
+        metrics::add_rows_written(inserted);
+        let mut response = data_response(
+            args,
+            format!(
+                "Inserted {} of {} records into table '{}':",
+                inserted,
+                records.len(),
+                table_name
+            ),
+            &results,
+        );
+        response["isError"] = json!(inserted == 0 && !records.is_empty());
+        response
     }
 }
 
-fn handle_search_table(table_name: &str, location: &str, query: &Map<String, Value>) -> Value {
-    let mut full_path = Path::new(location).join(table_name);
-    if full_path.extension().is_none() {
-        full_path.set_extension("db");
-    }
-
-    let path_str = full_path.to_string_lossy();
+fn handle_import_csv(table_name: &str, args: &Args, csv_text: &str) -> Value {
+    let mut rows = match parse_csv(csv_text) {
+        Ok(rows) => rows.into_iter(),
+        Err(e) => return ToolError::new("INVALID_CSV", e).into_value(),
+    };
+    let Some((_, header)) = rows.next() else {
+        return ToolError::new("INVALID_CSV", "The CSV has no header row.").into_value();
+    };
 
-    unsafe {
-        let pxdoc = pxlib::PX_new();
-        if pxdoc.is_null() {
-            return json!({ "isError": true, "content": [{ "type": "text", "text": "Failed to initialize PX library." }] });
-        }
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
 
-        let c_path = match CString::new(path_str.as_ref()) {
-            Ok(c) => c,
-            Err(_) => {
-                pxlib::PX_delete(pxdoc);
-                return json!({ "isError": true, "content": [{ "type": "text", "text": "Invalid table path string." }] });
-            }
-        };
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
+    doccache::invalidate(&full_path);
 
-        if pxlib::PX_open_file(pxdoc, c_path.as_ptr()) < 0 {
-            pxlib::PX_delete(pxdoc);
-            return json!({ "isError": true, "content": [{ "type": "text", "text": format!("Failed to open table '{}'", path_str) }] });
-        }
+    let mut doc = match PxDoc::new() {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    if let Err(e) = doc.open(&full_path, args.strict_types) {
+        return e.into_value();
+    }
+    let pxdoc = doc.as_ptr();
 
-        let num_records = pxlib::PX_get_num_records(pxdoc);
+    unsafe {
         let num_fields = pxlib::PX_get_num_fields(pxdoc);
         let fields_ptr = pxlib::PX_get_fields(pxdoc);
         let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
 
+        // Each column's target field, resolved once from the header.
+        let mut columns = Vec::with_capacity(header.len());
+        for name in &header {
+            let name = name.trim();
+            let field = fields_slice
+                .iter()
+                .find(|f| std::ffi::CStr::from_ptr(f.px_fname).to_string_lossy() == name);
+            match field {
+                Some(f) => columns.push((name.to_string(), f)),
+                None => {
+                    return ToolError::new(
+                        "FIELD_NOT_FOUND",
+                        format!("Table '{}' has no field '{}'.", table_name, name),
+                    )
+                    .into_value();
+                }
+            }
+        }
+
         let record_size = pxlib::PX_get_recordsize(pxdoc);
         let mut buf = vec![0u8; record_size as usize];
-        let mut results = Vec::new();
+        let mut autoinc = AutoInc::new(pxdoc, fields_slice);
+        let mut skipped = Vec::new();
+        let mut inserted = 0;
+        let mut total = 0;
+        for (line, cells) in rows {
+            total += 1;
+            if cells.len() != columns.len() {
+                skipped.push(json!({ "line": line, "error": format!(
+                    "Expected {} columns, found {}.",
+                    columns.len(),
+                    cells.len()
+                ) }));
+                continue;
+            }
+            let converted: Result<Map<String, Value>, String> = columns
+                .iter()
+                .zip(&cells)
+                .map(|((name, f), cell)| {
+                    csv_input_value(cell, f.px_ftype, f.px_fdc, args.date_format)
+                        .map(|v| (name.clone(), v))
+                        .map_err(|e| format!("Field '{}': {}", name, e))
+                })
+                .collect();
+            let record_data = match converted
+                .and_then(|r| protect_readonly_fields(args, table_name, &r).map_err(|e| e.message))
+            {
+                Ok((record_data, _)) => record_data,
+                Err(e) => {
+                    skipped.push(json!({ "line": line, "error": e }));
+                    continue;
+                }
+            };
 
-        for i in 0..num_records {
-            if !pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
-                let mut record_map = Map::new();
-                let mut matches = true;
+            buf.fill(0);
+            // A value the field can't hold (e.g. text outside the table's
+            // --encoding) would be written blank, so the row is skipped.
+            let warnings = fill_record_buffer(
+                pxdoc,
+                fields_slice,
+                &mut buf,
+                &record_data,
+                args.date_format,
+            );
+            if !warnings.is_empty() {
+                skipped.push(json!({ "line": line, "error": warnings.join(" ") }));
+                continue;
+            }
+            if let Some(autoinc) = &autoinc {
+                autoinc.fill(pxdoc, &mut buf);
+            }
+            let res = pxlib::PX_put_record(pxdoc, buf.as_mut_ptr() as *mut std::os::raw::c_char);
+            match px_result(res, PxOp::Write, table_name) {
+                Ok(()) => {
+                    inserted += 1;
+                    if let Some(autoinc) = &mut autoinc {
+                        autoinc.advance();
+                    }
+                }
+                Err(e) => skipped.push(json!({ "line": line, "error": e.message })),
+            }
+        }
+        if let (Some(autoinc), true) = (&autoinc, inserted > 0) {
+            autoinc.save(pxdoc);
+        }
 
-                let mut offset = 0;
-                for f_idx in 0..num_fields {
-                    let f = &fields_slice[f_idx as usize];
-                    let field_name = std::ffi::CStr::from_ptr(f.px_fname)
-                        .to_string_lossy()
-                        .into_owned();
-                    let field_type = f.px_ftype;
-                    let field_len = f.px_flen;
+        // Closing flushes the writes, so it must happen before reporting.
+        drop(doc);
+        if inserted > 0 {
+            refresh_primary_index(&full_path);
+        }
+
+        metrics::add_rows_written(inserted);
+        let mut response = data_response(
+            args,
+            format!(
+                "Imported {} of {} rows into table '{}' ({} skipped):",
+                inserted,
+                total,
+                table_name,
+                skipped.len()
+            ),
+            &json!({ "inserted": inserted, "skipped": skipped }),
+        );
+        response["isError"] = json!(inserted == 0 && total > 0);
+        response
+    }
+}
 
-                    let val =
-                        get_field_value(pxdoc, buf.as_mut_ptr().add(offset), field_type, field_len);
+/// Converts a CSV cell to the JSON value `put_field_value` expects for a
+/// field of `field_type`. Empty cells are blanks.
+fn csv_input_value(
+    cell: &str,
+    field_type: std::os::raw::c_char,
+    field_decimals: std::os::raw::c_int,
+    date_format: DateFormat,
+) -> Result<Value, String> {
+    if cell.is_empty() {
+        return Ok(Value::Null);
+    }
+    let trimmed = cell.trim();
+    match field_type as u32 {
+        pxlib::pxfAlpha => Ok(Value::String(cell.to_string())),
+        pxlib::pxfShort => trimmed
+            .parse::<i16>()
+            .map(|v| json!(v))
+            .map_err(|_| format!("'{}' is not a SHORT integer", cell)),
+        pxlib::pxfLong | pxlib::pxfAutoInc => trimmed
+            .parse::<i32>()
+            .map(|v| json!(v))
+            .map_err(|_| format!("'{}' is not a LONG integer", cell)),
+        pxlib::pxfNumber | pxlib::pxfCurrency => trimmed
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .map(|v| json!(v))
+            .ok_or_else(|| format!("'{}' is not a number", cell)),
+        pxlib::pxfLogical => match trimmed.to_ascii_lowercase().as_str() {
+            "true" | "t" | "yes" | "y" | "1" => Ok(Value::Bool(true)),
+            "false" | "f" | "no" | "n" | "0" => Ok(Value::Bool(false)),
+            _ => Err(format!("'{}' is not a LOGICAL value", cell)),
+        },
+        pxlib::pxfTime => {
+            let value = Value::String(trimmed.to_string());
+            dates::time_input_ms(&value, date_format)
+                .map(|_| value)
+                .ok_or_else(|| format!("'{}' is not a TIME of day", cell))
+        }
+        pxlib::pxfDate => {
+            let value = Value::String(trimmed.to_string());
+            dates::date_input_days(&value, date_format)
+                .map(|_| value)
+                .ok_or_else(|| format!("'{}' is not a DATE (YYYY-MM-DD)", cell))
+        }
+        pxlib::pxfTimestamp => {
+            let value = Value::String(trimmed.to_string());
+            dates::timestamp_input_ms(&value, date_format)
+                .map(|_| value)
+                .ok_or_else(|| format!("'{}' is not an RFC 3339 TIMESTAMP", cell))
+        }
+        pxlib::pxfBCD => {
+            let value = Value::String(trimmed.to_string());
+            bcd::from_json(&value, field_decimals.max(0) as usize).map(|_| value)
+        }
+        _ => Err(format!(
+            "{} fields can't be imported",
+            field_type_name(field_type)
+        )),
+    }
+}
 
-                    if let Some(query_val) = query.get(&field_name) {
-                        if !compare_values(&val, query_val) {
-                            matches = false;
-                        }
-                    }
-                    record_map.insert(field_name, val);
+/// Whether `--readonly-field` write-protects `field` of `table_name`.
+fn is_readonly_field(args: &Args, table_name: &str, field: &str) -> bool {
+    let table_stem = Path::new(table_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    args.readonly_fields
+        .iter()
+        .any(|spec| match spec.split_once('.') {
+            Some((t, f)) => t.eq_ignore_ascii_case(&table_stem) && f.eq_ignore_ascii_case(field),
+            None => false,
+        })
+}
 
-                    offset += field_len as usize;
-                }
+/// Applies `--readonly-field` protections to a write. In reject mode any
+/// protected field fails the write; in ignore mode those fields are dropped
+/// and returned so the caller can report them.
+fn protect_readonly_fields(
+    args: &Args,
+    table_name: &str,
+    record_data: &Map<String, Value>,
+) -> Result<(Map<String, Value>, Vec<String>), ToolError> {
+    let blocked: Vec<String> = record_data
+        .keys()
+        .filter(|field| is_readonly_field(args, table_name, field))
+        .cloned()
+        .collect();
 
-                if matches {
-                    results.push(Value::Object(record_map));
-                }
+    if blocked.is_empty() {
+        return Ok((record_data.clone(), blocked));
+    }
+    match args.readonly_field_mode {
+        ReadonlyFieldMode::Reject => Err(ToolError::new(
+            "FIELD_READ_ONLY",
+            format!(
+                "Write rejected: fields are write-protected in table '{}': {}.",
+                table_name,
+                blocked.join(", ")
+            ),
+        )),
+        ReadonlyFieldMode::Ignore => {
+            let mut filtered = record_data.clone();
+            for field in &blocked {
+                filtered.remove(field);
             }
-            if results.len() >= 1000 {
-                break;
-            } // Safety limit
+            Ok((filtered, blocked))
         }
+    }
+}
 
-        pxlib::PX_close(pxdoc);
-        pxlib::PX_delete(pxdoc);
+/// Reads record `idx` into `buf` for an in-place update, distinguishing an
+/// index past the end, a deleted record, and a failed read so callers can
+/// pick the right recovery (re-search, give up, or retry).
+unsafe fn load_existing_record(
+    pxdoc: *mut pxlib::pxdoc_t,
+    idx: std::os::raw::c_int,
+    buf: &mut [u8],
+) -> Result<(), ToolError> {
+    let num_records = pxlib::PX_get_num_records(pxdoc);
+    if idx >= num_records {
+        return Err(ToolError::new(
+            "INDEX_OUT_OF_RANGE",
+            format!(
+                "Record index {} is out of range (table has {} records).",
+                idx, num_records
+            ),
+        ));
+    }
+    if !pxlib::PX_get_record(pxdoc, idx, buf.as_mut_ptr()).is_null() {
+        return Ok(());
+    }
 
-        json!({
-            "content": [
-                { "type": "text", "text": format!("Search results for table '{}' ({} found):", table_name, results.len()) },
-                { "type": "text", "text": serde_json::to_string_pretty(&results).unwrap() }
-            ]
-        })
+    // Ask pxlib to return the record even if deleted, and whether it was.
+    let mut deleted: std::os::raw::c_int = 1;
+    let mut block_info: pxlib::pxdatablockinfo_t = std::mem::zeroed();
+    let found = !pxlib::PX_get_record2(
+        pxdoc,
+        idx,
+        buf.as_mut_ptr() as *mut std::os::raw::c_char,
+        &mut deleted,
+        &mut block_info,
+    )
+    .is_null();
+    if found && deleted != 0 {
+        Err(ToolError::new(
+            "RECORD_DELETED",
+            format!("Record at index {} has been deleted.", idx),
+        ))
+    } else {
+        Err(ToolError::new(
+            "READ_ERROR",
+            format!("Failed to read record at index {}.", idx),
+        ))
     }
 }
 
-fn handle_create_table(table_name: &str, location: &str, fields: &Vec<Value>) -> Value {
-    let mut full_path = Path::new(location).join(table_name);
-    if full_path.extension().is_none() {
-        full_path.set_extension("db");
+/// Placeholder value and format guidance for a field in a `prepare_insert`
+/// skeleton, or `None` for types `insert_record` can't write yet.
+fn insert_placeholder(
+    ftype: std::os::raw::c_char,
+    flen: i32,
+    decimals: i32,
+) -> Option<(Value, String)> {
+    match ftype as u32 {
+        pxlib::pxfAlpha => Some((json!(""), format!("text, at most {} characters", flen))),
+        pxlib::pxfShort => Some((json!(0), "integer from -32767 to 32767".to_string())),
+        pxlib::pxfLong => Some((
+            json!(0),
+            "integer from -2147483647 to 2147483647".to_string(),
+        )),
+        pxlib::pxfNumber => Some((json!(0.0), "number".to_string())),
+        pxlib::pxfCurrency => Some((json!(0.0), "number (currency amount)".to_string())),
+        pxlib::pxfLogical => Some((json!(false), "true or false".to_string())),
+        pxlib::pxfTime => Some((
+            json!("00:00:00"),
+            "time of day as \"HH:MM:SS[.mmm]\", or a number in the --date-format unit".to_string(),
+        )),
+        pxlib::pxfBCD => Some((
+            json!("0"),
+            format!(
+                "decimal with up to {} decimal places, as a number or, beyond 15 digits, a string",
+                decimals
+            ),
+        )),
+        pxlib::pxfDate => Some((
+            json!("2000-01-01"),
+            "date as \"YYYY-MM-DD\", or a number in the --date-format unit".to_string(),
+        )),
+        pxlib::pxfTimestamp => Some((
+            json!("2000-01-01T00:00:00Z"),
+            "RFC 3339 date and time, or a number in the --date-format unit".to_string(),
+        )),
+        _ => None,
     }
+}
 
-    let path_str = full_path.to_string_lossy();
+fn handle_prepare_insert(table_name: &str, args: &Args) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
 
-    #[repr(C)]
-    struct PxField {
-        px_fname: *mut std::os::raw::c_char,
-        px_ftype: std::os::raw::c_char,
-        px_flen: std::os::raw::c_int,
-        px_fdc: std::os::raw::c_int,
-    }
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
 
-    extern "C" {
-        fn malloc(size: usize) -> *mut std::ffi::c_void;
-        fn strdup(s: *const std::os::raw::c_char) -> *mut std::os::raw::c_char;
-    }
+    let doc = match doccache::open(&full_path, args) {
+        Ok(doc) => doc,
+        Err(e) => return e.into_value(),
+    };
+    let pxdoc = doc.as_ptr();
 
     unsafe {
-        let pxdoc = pxlib::PX_new();
-        if pxdoc.is_null() {
-            return json!({ "isError": true, "content": [{ "type": "text", "text": "Failed to initialize PX library." }] });
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+
+        let mut record = Map::new();
+        let mut guidance = Map::new();
+        let mut excluded = Vec::new();
+        for f in fields_slice {
+            let name = std::ffi::CStr::from_ptr(f.px_fname)
+                .to_string_lossy()
+                .into_owned();
+            let type_name = field_type_name(f.px_ftype);
+            if f.px_ftype as u32 == pxlib::pxfAutoInc {
+                excluded.push(
+                    json!({ "field": name, "reason": "AUTOINC values are generated on insert" }),
+                );
+                continue;
+            }
+            if is_readonly_field(args, table_name, &name) {
+                excluded.push(
+                    json!({ "field": name, "reason": "write-protected by --readonly-field" }),
+                );
+                continue;
+            }
+            match insert_placeholder(f.px_ftype, f.px_flen, f.px_fdc) {
+                Some((placeholder, format)) => {
+                    record.insert(name.clone(), placeholder);
+                    guidance.insert(name, json!({ "type": type_name, "format": format }));
+                }
+                None => excluded.push(json!({
+                    "field": name,
+                    "reason": format!("{} fields are not yet writable by insert_record", type_name)
+                })),
+            }
         }
 
-        let fields_byte_size = std::mem::size_of::<PxField>() * fields.len();
-        let px_fields_ptr = malloc(fields_byte_size) as *mut PxField;
+        data_response(
+            args,
+            format!(
+                "Insert template for table '{}': fill in `record` and pass it to insert_record.",
+                table_name
+            ),
+            &json!({ "record": record, "fields": guidance, "excluded": excluded }),
+        )
+    }
+}
 
-        for (i, f_val) in fields.iter().enumerate() {
-            let name_str = f_val
-                .get("name")
-                .and_then(|v| v.as_str())
-                .unwrap_or("UNKNOWN");
-            let type_str = f_val
-                .get("type")
-                .and_then(|v| v.as_str())
-                .unwrap_or("ALPHA");
-            let length = f_val.get("length").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
-
-            // Allocate string using C's strdup so pxlib can free it
-            let c_name =
-                CString::new(name_str).unwrap_or_else(|_| CString::new("INVALID").unwrap());
-            let c_name_ptr = strdup(c_name.as_ptr());
-
-            let f_type = match type_str.to_uppercase().as_str() {
-                "ALPHA" => pxlib::pxfAlpha,
-                "DATE" => pxlib::pxfDate,
-                "SHORT" => pxlib::pxfShort,
-                "LONG" => pxlib::pxfLong,
-                "CURRENCY" => pxlib::pxfCurrency,
-                "NUMBER" => pxlib::pxfNumber,
-                "LOGICAL" => pxlib::pxfLogical,
-                "MEMO" => pxlib::pxfMemoBLOb,
-                "BLOB" => pxlib::pxfBLOb,
-                "TIME" => pxlib::pxfTime,
-                "TIMESTAMP" => pxlib::pxfTimestamp,
-                "AUTOINC" => pxlib::pxfAutoInc,
-                "BCD" => pxlib::pxfBCD,
-                "BYTES" => pxlib::pxfBytes,
-                _ => pxlib::pxfAlpha,
-            };
+/// Quotes a CSV cell when it contains a delimiter, quote or line break.
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
 
-            let final_length = if length > 0 {
-                length
-            } else {
-                match f_type as u32 {
-                    pxlib::pxfShort => 2,
-                    pxlib::pxfLong | pxlib::pxfAutoInc | pxlib::pxfDate | pxlib::pxfTime => 4,
-                    pxlib::pxfCurrency | pxlib::pxfNumber | pxlib::pxfTimestamp => 8,
-                    pxlib::pxfLogical => 1,
-                    _ => 0,
+/// Splits CSV text (RFC 4180: quoted cells may hold delimiters, doubled
+/// quotes and line breaks) into rows, each with the 1-based line it starts
+/// on. Blank lines are skipped.
+fn parse_csv(text: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    cell.push('"');
+                } else {
+                    in_quotes = false;
                 }
-            };
-
-            // Write into the malloc'd array directly to avoid double free
-            std::ptr::write(
-                px_fields_ptr.add(i),
-                PxField {
-                    px_fname: c_name_ptr,
-                    px_ftype: f_type as std::os::raw::c_char,
-                    px_flen: final_length,
-                    px_fdc: 0,
-                },
-            );
+            }
+            '"' if cell.is_empty() => in_quotes = true,
+            '\n' if in_quotes => {
+                line += 1;
+                cell.push(c);
+            }
+            _ if in_quotes => cell.push(c),
+            ',' => row.push(std::mem::take(&mut cell)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                row.push(std::mem::take(&mut cell));
+                if row.len() > 1 || !row[0].is_empty() {
+                    rows.push((row_line, std::mem::take(&mut row)));
+                }
+                row.clear();
+                line += 1;
+                row_line = line;
+            }
+            _ => cell.push(c),
         }
+    }
+    if in_quotes {
+        return Err(format!(
+            "Unterminated quoted cell in the row starting on line {}.",
+            row_line
+        ));
+    }
+    if !row.is_empty() || !cell.is_empty() {
+        row.push(cell);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => csv_escape(s),
+        other => csv_escape(&other.to_string()),
+    }
+}
+
+/// Scans the table and hands each CSV line (header first, newline
+/// included) to `emit` as soon as it's produced, so a streaming transport
+/// can forward rows without buffering the export. Returns the row count.
+pub fn stream_table_csv(
+    table_name: &str,
+    args: &Args,
+    emit: &mut dyn FnMut(&str),
+) -> Result<usize, ToolError> {
+    let full_path = resolve_table_path(table_name, args)?;
+
+    let _lock = locks::acquire(&full_path, args.lock_timeout_ms)?;
+
+    let doc = doccache::open(&full_path, args)?;
+    let pxdoc = doc.as_ptr();
+
+    unsafe {
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let fields_ptr = pxlib::PX_get_fields(pxdoc);
+        let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
 
-        let c_path = match CString::new(path_str.as_ref()) {
-            Ok(c) => c,
-            Err(_) => {
-                pxlib::PX_delete(pxdoc);
-                return json!({ "isError": true, "content": [{ "type": "text", "text": "Invalid table path string." }] });
-            }
-        };
+        let header: Vec<String> = fields_slice
+            .iter()
+            .map(|f| csv_escape(&std::ffi::CStr::from_ptr(f.px_fname).to_string_lossy()))
+            .collect();
+        emit(&format!("{}\n", header.join(",")));
 
-        // File type 0 = pxfFileTypIndexDB
-        let res = pxlib::PX_create_file(
-            pxdoc,
-            px_fields_ptr as *mut pxlib::pxfield_t,
-            fields.len() as i32,
-            c_path.as_ptr(),
-            0,
-        );
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let mut buf = vec![0u8; record_size as usize];
+        let mut rows = 0;
+        for i in 0..num_records {
+            if pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                continue;
+            }
+            let mut record_map = decode_record(pxdoc, fields_slice, &mut buf);
+            if args.integral_numbers_as_int {
+                integral_numbers_as_int(&mut record_map, fields_slice);
+            }
+            let cells: Vec<String> = record_map.values().map(csv_cell).collect();
+            emit(&format!("{}\n", cells.join(",")));
+            rows += 1;
+        }
 
-        // We MUST close the document to ensure the header and data are flushed.
-        pxlib::PX_close(pxdoc);
-        // Delete frees pxdoc and its internal pointers (like the fields array we allocated with malloc).
-        pxlib::PX_delete(pxdoc);
+        metrics::add_rows_read(rows);
+        Ok(rows)
+    }
+}
 
-        if res >= 0 {
-            json!({
-                "content": [{ "type": "text", "text": format!("Successfully created table '{}' with {} fields.", table_name, fields.len()) }]
-            })
-        } else {
-            json!({
-                "isError": true,
-                "content": [{ "type": "text", "text": format!("Failed to create table '{}'.", table_name) }]
-            })
+/// Buffered export for transports that can't stream, such as stdio: the
+/// whole CSV is returned as one content block.
+fn handle_export_table_csv(table_name: &str, args: &Args) -> Value {
+    let mut csv = String::new();
+    match stream_table_csv(table_name, args, &mut |line| csv.push_str(line)) {
+        Ok(rows) => {
+            let summary = format!("CSV export of table '{}' ({} records):", table_name, rows);
+            if args.json_output_only {
+                json!({ "content": [{ "type": "text", "text": csv }] })
+            } else {
+                json!({
+                    "content": [
+                        { "type": "text", "text": summary },
+                        { "type": "text", "text": csv }
+                    ]
+                })
+            }
         }
+        Err(e) => e.into_value(),
     }
 }
 
-fn handle_write_record(
+/// Writes the export to `output_path` as it is produced, so large tables
+/// never sit in memory. An existing file is only replaced with `overwrite`.
+fn handle_export_table_csv_file(
     table_name: &str,
-    location: &str,
-    index: Option<i32>,
-    record_data: &Map<String, Value>,
+    args: &Args,
+    output_path: &str,
+    overwrite: bool,
 ) -> Value {
-    let mut full_path = Path::new(location).join(table_name);
-    if full_path.extension().is_none() {
-        full_path.set_extension("db");
+    let path = match resolve_location_file(output_path, args, "INVALID_OUTPUT_PATH") {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+    if path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ["db", "px", "mb"].contains(&e.to_ascii_lowercase().as_str()))
+    {
+        return ToolError::new(
+            "INVALID_OUTPUT_PATH",
+            format!(
+                "Refusing to write CSV to '{}': it names a Paradox file.",
+                path.display()
+            ),
+        )
+        .into_value();
+    }
+    let file = match std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!overwrite)
+        .open(&path)
+    {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return ToolError::new(
+                "OUTPUT_EXISTS",
+                format!(
+                    "'{}' already exists; pass overwrite: true to replace it.",
+                    path.display()
+                ),
+            )
+            .into_value();
+        }
+        Err(e) => {
+            return ToolError::new(
+                "EXPORT_WRITE_FAILED",
+                format!("Failed to create '{}': {}", path.display(), e),
+            )
+            .into_value();
+        }
+    };
+
+    let mut writer = std::io::BufWriter::new(file);
+    let mut write_error = None;
+    let outcome = stream_table_csv(table_name, args, &mut |line| {
+        if write_error.is_none() {
+            write_error = writer.write_all(line.as_bytes()).err();
+        }
+    });
+    let rows = match outcome {
+        Ok(rows) => rows,
+        Err(e) => {
+            // Don't leave an empty or partial export behind.
+            drop(writer);
+            let _ = std::fs::remove_file(&path);
+            return e.into_value();
+        }
+    };
+    if let Some(e) = write_error.or_else(|| writer.flush().err()) {
+        return ToolError::new(
+            "EXPORT_WRITE_FAILED",
+            format!("Failed to write '{}': {}", path.display(), e),
+        )
+        .into_value();
     }
 
+    json!({ "content": [{ "type": "text", "text": format!(
+        "Successfully exported {} records of table '{}' to '{}'.",
+        rows,
+        table_name,
+        path.display()
+    ) }] })
+}
+
+fn handle_inspect_value(table_name: &str, args: &Args, field: &str, value: &Value) -> Value {
+    let full_path = match resolve_table_path(table_name, args) {
+        Ok(path) => path,
+        Err(e) => return e.into_value(),
+    };
+
     let path_str = full_path.to_string_lossy();
+    let _lock = match locks::acquire(&full_path, args.lock_timeout_ms) {
+        Ok(lock) => lock,
+        Err(e) => return e.into_value(),
+    };
 
     unsafe {
-        let pxdoc = pxlib::PX_new();
+        let pxdoc = pxerror::new_doc();
         if pxdoc.is_null() {
-            return json!({ "isError": true, "content": [{ "type": "text", "text": "Failed to initialize PX library." }] });
+            return px_init_error().into_value();
         }
 
         let c_path = match CString::new(path_str.as_ref()) {
             Ok(c) => c,
             Err(_) => {
                 pxlib::PX_delete(pxdoc);
-                return json!({ "isError": true, "content": [{ "type": "text", "text": "Invalid table path string." }] });
+                return invalid_path_error().into_value();
             }
         };
 
-        if pxlib::PX_open_file(pxdoc, c_path.as_ptr()) < 0 {
+        if let Err(e) = px_result(
+            pxlib::PX_open_file(pxdoc, c_path.as_ptr()),
+            PxOp::Open,
+            &path_str,
+        ) {
             pxlib::PX_delete(pxdoc);
-            return json!({ "isError": true, "content": [{ "type": "text", "text": format!("Failed to open table '{}' for writing. Ensure it's not locked.", path_str) }] });
+            return e.into_value();
         }
 
         let num_fields = pxlib::PX_get_num_fields(pxdoc);
         let fields_ptr = pxlib::PX_get_fields(pxdoc);
         let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
-        let record_size = pxlib::PX_get_recordsize(pxdoc);
-        let mut buf = vec![0u8; record_size as usize];
+        let target = fields_slice
+            .iter()
+            .find(|f| std::ffi::CStr::from_ptr(f.px_fname).to_string_lossy() == field);
+        let Some(f) = target else {
+            pxlib::PX_close(pxdoc);
+            pxlib::PX_delete(pxdoc);
+            return ToolError::new(
+                "FIELD_NOT_FOUND",
+                format!("Field '{}' not found in table '{}'.", field, table_name),
+            )
+            .into_value();
+        };
 
-        if let Some(idx) = index {
-            if pxlib::PX_get_record(pxdoc, idx, buf.as_mut_ptr()).is_null() {
-                pxlib::PX_close(pxdoc);
-                pxlib::PX_delete(pxdoc);
-                return json!({ "isError": true, "content": [{ "type": "text", "text": format!("Record at index {} not found.", idx) }] });
-            }
+        // Round-trip through a scratch buffer; the table itself is never written.
+        let mut scratch = vec![0u8; f.px_flen.max(0) as usize];
+        let put_warning = put_field_value(
+            pxdoc,
+            scratch.as_mut_ptr(),
+            f.px_ftype,
+            f.px_flen,
+            f.px_fdc,
+            value,
+            args.date_format,
+        );
+        let stored_bytes: Vec<String> = scratch.iter().map(|b| format!("{:02x}", b)).collect();
+        let read_back =
+            get_field_value(pxdoc, scratch.as_mut_ptr(), f.px_ftype, f.px_flen, f.px_fdc);
+        let type_str = field_type_name(f.px_ftype);
+        let flen = f.px_flen;
+
+        pxlib::PX_close(pxdoc);
+        pxlib::PX_delete(pxdoc);
+
+        let mut warnings = Vec::new();
+        if let Some(warning) = put_warning {
+            warnings.push(format!("Nothing was stored: {}.", warning));
+        } else if read_back != *value {
+            warnings.push(format!(
+                "Value was coerced: {} reads back as {}.",
+                value, read_back
+            ));
         }
 
-        let mut offset = 0;
-        for f_idx in 0..num_fields {
-            let f = &fields_slice[f_idx as usize];
-            let field_name = std::ffi::CStr::from_ptr(f.px_fname)
-                .to_string_lossy()
-                .into_owned();
-            let field_type = f.px_ftype;
-            let field_len = f.px_flen;
+        let report = json!({
+            "field": field,
+            "type": type_str,
+            "length": flen,
+            "input": value,
+            "stored_bytes": stored_bytes.join(" "),
+            "read_back": read_back,
+            "warnings": warnings
+        });
+        json!({
+            "content": [
+                { "type": "text", "text": format!("Round-trip of {} through field '{}' ({}):", value, field, type_str) },
+                { "type": "text", "text": serde_json::to_string_pretty(&report).unwrap() }
+            ]
+        })
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Decodes every field of the record currently held in `buf`.
+unsafe fn decode_record(
+    pxdoc: *mut pxlib::pxdoc_t,
+    fields: &[pxlib::pxfield_t],
+    buf: &mut [u8],
+) -> Map<String, Value> {
+    decode_record_at(pxdoc, fields, buf, &cumulative_offsets(fields))
+}
+
+/// Decodes a record whose fields start at the given byte `offsets`.
+unsafe fn decode_record_at(
+    pxdoc: *mut pxlib::pxdoc_t,
+    fields: &[pxlib::pxfield_t],
+    buf: &mut [u8],
+    offsets: &[usize],
+) -> Map<String, Value> {
+    let mut record_map = Map::new();
+    for (f, &offset) in fields.iter().zip(offsets) {
+        let field_name = std::ffi::CStr::from_ptr(f.px_fname)
+            .to_string_lossy()
+            .into_owned();
+        let val = get_field_value(
+            pxdoc,
+            buf.as_mut_ptr().add(offset),
+            f.px_ftype,
+            f.px_flen,
+            f.px_fdc,
+        );
+        record_map.insert(field_name, val);
+    }
+    record_map
+}
+
+/// The naive layout: each field starts where the previous one ends.
+fn cumulative_offsets(fields: &[pxlib::pxfield_t]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(fields.len());
+    let mut offset = 0;
+    for f in fields {
+        offsets.push(offset);
+        offset += f.px_flen as usize;
+    }
+    offsets
+}
+
+/// Encodes the fields present in `record_data` into a raw record buffer,
+/// leaving bytes of absent fields untouched. Returns a warning for each
+/// value that couldn't be encoded.
+unsafe fn fill_record_buffer(
+    pxdoc: *mut pxlib::pxdoc_t,
+    fields: &[pxlib::pxfield_t],
+    buf: &mut [u8],
+    record_data: &Map<String, Value>,
+    date_format: DateFormat,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut offset = 0;
+    for f in fields {
+        let field_name = std::ffi::CStr::from_ptr(f.px_fname)
+            .to_string_lossy()
+            .into_owned();
+        let field_type = f.px_ftype;
+        let field_len = f.px_flen;
 
-            if let Some(val) = record_data.get(&field_name) {
-                // Add the offset to the base buffer pointer
-                let field_ptr = buf.as_mut_ptr().add(offset as usize);
-                put_field_value(pxdoc, field_ptr, field_type, field_len, val);
+        if let Some(val) = record_data.get(&field_name) {
+            // Add the offset to the base buffer pointer
+            let field_ptr = buf.as_mut_ptr().add(offset as usize);
+            if let Some(warning) = put_field_value(
+                pxdoc,
+                field_ptr,
+                field_type,
+                field_len,
+                f.px_fdc,
+                val,
+                date_format,
+            ) {
+                warnings.push(format!(
+                    "Field '{}' left unchanged: {}.",
+                    field_name, warning
+                ));
             }
+        }
+
+        offset += field_len;
+    }
+    warnings
+}
+
+/// Numbers the AUTOINC fields of records being appended. pxlib leaves them
+/// to the caller (and `put_field_value` ignores values supplied for them),
+/// so each new record gets one past both the header counter and the largest
+/// value already stored, which keeps a stale counter from handing out a
+/// duplicate.
+struct AutoInc {
+    /// Offset and length of each AUTOINC field in the record buffer.
+    fields: Vec<(usize, std::os::raw::c_int)>,
+    next: i32,
+}
 
-            offset += field_len;
+impl AutoInc {
+    /// `None` when the table has no AUTOINC field.
+    unsafe fn new(pxdoc: *mut pxlib::pxdoc_t, fields: &[pxlib::pxfield_t]) -> Option<Self> {
+        let mut offset = 0;
+        let mut autoinc_fields = Vec::new();
+        for f in fields {
+            if f.px_ftype as u32 == pxlib::pxfAutoInc {
+                autoinc_fields.push((offset, f.px_flen));
+            }
+            offset += f.px_flen as usize;
+        }
+        if autoinc_fields.is_empty() {
+            return None;
         }
 
-        let res = if let Some(idx) = index {
-            pxlib::PX_put_recordn(pxdoc, buf.as_mut_ptr() as *mut std::os::raw::c_char, idx)
+        let mut counter: std::os::raw::c_float = 0.0;
+        let mut last = if pxlib::PX_get_value(pxdoc, c"autoinc".as_ptr(), &mut counter) == 0 {
+            counter as i32
         } else {
-            pxlib::PX_put_record(pxdoc, buf.as_mut_ptr() as *mut std::os::raw::c_char)
+            pxerror::take_error();
+            0
         };
+        let mut buf = vec![0u8; pxlib::PX_get_recordsize(pxdoc) as usize];
+        for i in 0..pxlib::PX_get_num_records(pxdoc) {
+            if pxlib::PX_get_record(pxdoc, i, buf.as_mut_ptr()).is_null() {
+                continue;
+            }
+            for &(offset, len) in &autoinc_fields {
+                let mut value: std::os::raw::c_long = 0;
+                let ptr = buf.as_mut_ptr().add(offset) as *mut std::os::raw::c_char;
+                if pxlib::PX_get_data_long(pxdoc, ptr, len, &mut value) > 0 {
+                    last = last.max(value as i32);
+                }
+            }
+        }
+        Some(AutoInc {
+            fields: autoinc_fields,
+            next: last + 1,
+        })
+    }
 
-        pxlib::PX_close(pxdoc);
-        pxlib::PX_delete(pxdoc);
+    /// Writes the next value into every AUTOINC field of `buf`.
+    unsafe fn fill(&self, pxdoc: *mut pxlib::pxdoc_t, buf: &mut [u8]) {
+        for &(offset, len) in &self.fields {
+            pxlib::PX_put_data_long(
+                pxdoc,
+                buf.as_mut_ptr().add(offset) as *mut std::os::raw::c_char,
+                len,
+                self.next as std::os::raw::c_int,
+            );
+        }
+    }
 
-        if res >= 0 {
-            json!({
-                "content": [{ "type": "text", "text": format!("Successfully {} record in table '{}'.", if index.is_some() { "updated" } else { "inserted" }, table_name) }]
-            })
-        } else {
-            json!({
-                "isError": true,
-                "content": [{ "type": "text", "text": format!("Failed to write record to table '{}'.", table_name) }]
-            })
+    /// Marks the value written by the last `fill` as used.
+    fn advance(&mut self) {
+        self.next += 1;
+    }
+
+    /// Stores the last assigned value as the header counter. Not every
+    /// pxlib build lets it be set, so a refusal is only logged.
+    unsafe fn save(&self, pxdoc: *mut pxlib::pxdoc_t) {
+        let last = (self.next - 1) as std::os::raw::c_float;
+        if pxlib::PX_set_value(pxdoc, c"autoinc".as_ptr(), last) < 0 {
+            log::debug!(
+                "Could not update the AUTOINC counter: {}",
+                pxerror::take_error().unwrap_or_default()
+            );
+        }
+    }
+}
+
+/// Reads back the AUTOINC values of record `index`, keyed by field name.
+unsafe fn read_autoinc_values(
+    pxdoc: *mut pxlib::pxdoc_t,
+    fields: &[pxlib::pxfield_t],
+    buf: &mut [u8],
+    index: std::os::raw::c_int,
+) -> Map<String, Value> {
+    let mut autoinc_values = Map::new();
+    if pxlib::PX_get_record(pxdoc, index, buf.as_mut_ptr()).is_null() {
+        return autoinc_values;
+    }
+    let mut offset = 0;
+    for f in fields {
+        if f.px_ftype as u32 == pxlib::pxfAutoInc {
+            let field_name = std::ffi::CStr::from_ptr(f.px_fname)
+                .to_string_lossy()
+                .into_owned();
+            let val = get_field_value(
+                pxdoc,
+                buf.as_mut_ptr().add(offset),
+                f.px_ftype,
+                f.px_flen,
+                f.px_fdc,
+            );
+            autoinc_values.insert(field_name, val);
         }
+        offset += f.px_flen as usize;
     }
+    autoinc_values
 }
 
+/// Decodes the field at `buf_ptr`. `field_decimals` is the field's
+/// `px_fdc`, which only BCD decoding uses.
 unsafe fn get_field_value(
     pxdoc: *mut pxlib::pxdoc_t,
     buf_ptr: *mut u8,
     field_type: std::os::raw::c_char,
     field_len: std::os::raw::c_int,
+    field_decimals: std::os::raw::c_int,
 ) -> Value {
     match field_type as u32 {
         pxlib::pxfAlpha => {
@@ -784,25 +5723,25 @@ unsafe fn get_field_value(
                 buf_ptr as *mut std::os::raw::c_char,
                 field_len,
                 &mut val_ptr,
-            ) >= 0
+            ) > 0
                 && !val_ptr.is_null()
             {
-                let s = std::ffi::CStr::from_ptr(val_ptr)
-                    .to_string_lossy()
-                    .into_owned();
-                Value::String(s)
+                let bytes = std::ffi::CStr::from_ptr(val_ptr).to_bytes();
+                Value::String(charset::decode(pxdoc, bytes))
             } else {
                 Value::Null
             }
         }
         pxlib::pxfShort => {
             let mut v: std::os::raw::c_short = 0;
+            // pxlib returns 0 for a blank field, which is null rather than a
+            // zero, and 1 for a value.
             if pxlib::PX_get_data_short(
                 pxdoc,
                 buf_ptr as *mut std::os::raw::c_char,
                 field_len,
                 &mut v,
-            ) >= 0
+            ) > 0
             {
                 json!(v)
             } else {
@@ -816,13 +5755,104 @@ unsafe fn get_field_value(
                 buf_ptr as *mut std::os::raw::c_char,
                 field_len,
                 &mut v,
-            ) >= 0
+            ) > 0
             {
                 json!(v)
             } else {
                 Value::Null
             }
         }
+        pxlib::pxfTime => {
+            let mut v: std::os::raw::c_long = 0;
+            // pxlib returns 0 for a blank field, 1 for a value.
+            if pxlib::PX_get_data_long(
+                pxdoc,
+                buf_ptr as *mut std::os::raw::c_char,
+                field_len,
+                &mut v,
+            ) > 0
+            {
+                Value::String(dates::format_time_of_day(v as i64))
+            } else {
+                Value::Null
+            }
+        }
+        pxlib::pxfDate => {
+            let mut v: std::os::raw::c_long = 0;
+            // As with TIME, 0 means a blank field.
+            if pxlib::PX_get_data_long(
+                pxdoc,
+                buf_ptr as *mut std::os::raw::c_char,
+                field_len,
+                &mut v,
+            ) > 0
+            {
+                Value::String(dates::format_paradox_date(v as i64))
+            } else {
+                Value::Null
+            }
+        }
+        pxlib::pxfMemoBLOb | pxlib::pxfFmtMemoBLOb => {
+            let mut modnr: std::os::raw::c_int = 0;
+            let mut size: std::os::raw::c_int = 0;
+            // NULL for a blank memo, or when no `.mb` file is attached.
+            let blob = pxlib::PX_get_data_blob(
+                pxdoc,
+                buf_ptr as *const std::os::raw::c_char,
+                field_len,
+                &mut modnr,
+                &mut size,
+            );
+            if blob.is_null() {
+                Value::Null
+            } else {
+                let bytes = std::slice::from_raw_parts(blob as *const u8, size.max(0) as usize);
+                let text = String::from_utf8_lossy(bytes).into_owned();
+                free(blob as *mut std::ffi::c_void);
+                Value::String(text)
+            }
+        }
+        pxlib::pxfBLOb => {
+            let mut modnr: std::os::raw::c_int = 0;
+            let mut size: std::os::raw::c_int = 0;
+            let blob = pxlib::PX_get_data_blob(
+                pxdoc,
+                buf_ptr as *const std::os::raw::c_char,
+                field_len,
+                &mut modnr,
+                &mut size,
+            );
+            if blob.is_null() {
+                Value::Null
+            } else {
+                let bytes = std::slice::from_raw_parts(blob as *const u8, size.max(0) as usize);
+                let encoded = base64::encode(bytes);
+                free(blob as *mut std::ffi::c_void);
+                Value::String(encoded)
+            }
+        }
+        // BYTES are stored inline, so the record buffer holds them as is.
+        pxlib::pxfBytes => {
+            let bytes = std::slice::from_raw_parts(buf_ptr, field_len.max(0) as usize);
+            Value::String(base64::encode(bytes))
+        }
+        pxlib::pxfTimestamp => {
+            let mut v: f64 = 0.0;
+            if pxlib::PX_get_data_double(
+                pxdoc,
+                buf_ptr as *mut std::os::raw::c_char,
+                field_len,
+                &mut v,
+            ) > 0
+            {
+                // Out-of-range values are passed through rather than dropped.
+                dates::format_paradox_timestamp(v)
+                    .map(Value::String)
+                    .unwrap_or_else(|| json!(v))
+            } else {
+                Value::Null
+            }
+        }
         pxlib::pxfNumber | pxlib::pxfCurrency => {
             let mut v: f64 = 0.0;
             if pxlib::PX_get_data_double(
@@ -830,7 +5860,7 @@ unsafe fn get_field_value(
                 buf_ptr as *mut std::os::raw::c_char,
                 field_len,
                 &mut v,
-            ) >= 0
+            ) > 0
             {
                 json!(v)
             } else {
@@ -844,87 +5874,336 @@ unsafe fn get_field_value(
                 buf_ptr as *mut std::os::raw::c_char,
                 field_len,
                 &mut v,
-            ) >= 0
+            ) > 0
             {
                 Value::Bool(v != 0)
             } else {
                 Value::Null
             }
         }
+        pxlib::pxfBCD => {
+            let mut val_ptr: *mut std::os::raw::c_char = std::ptr::null_mut();
+            // pxlib's BCD accessors take the decimal count where the others
+            // take the field length.
+            if pxlib::PX_get_data_bcd(pxdoc, buf_ptr, field_decimals, &mut val_ptr) > 0
+                && !val_ptr.is_null()
+            {
+                let s = std::ffi::CStr::from_ptr(val_ptr)
+                    .to_string_lossy()
+                    .into_owned();
+                free(val_ptr as *mut std::ffi::c_void);
+                bcd::to_json(&s)
+            } else {
+                Value::Null
+            }
+        }
         _ => Value::String(format!("<type {}>", field_type)),
     }
 }
 
+extern "C" {
+    /// pxlib's default deallocator, for memory it hands back to callers.
+    fn free(ptr: *mut std::ffi::c_void);
+}
+
+/// Encodes `val` into the field at `buf_ptr`. A value of a JSON type the
+/// field doesn't accept, a DATE, TIME, TIMESTAMP or BCD value that doesn't
+/// parse, or ALPHA text the table's charset can't store, leaves the field
+/// untouched and comes back as a warning instead.
 unsafe fn put_field_value(
     pxdoc: *mut pxlib::pxdoc_t,
     buf_ptr: *mut u8,
     field_type: std::os::raw::c_char,
     field_len: std::os::raw::c_int,
+    field_decimals: std::os::raw::c_int,
     val: &Value,
-) {
+    date_format: DateFormat,
+) -> Option<String> {
+    if val.is_null() {
+        // Paradox marks a blank field of any type with all-zero bytes.
+        // Stored numbers have their sign bit flipped, so a real 0 is never
+        // all zeros and can't be mistaken for a blank.
+        std::ptr::write_bytes(buf_ptr, 0, field_len as usize);
+        return None;
+    }
+    let rejected = || {
+        Some(format!(
+            "a JSON {} is not accepted for {} fields",
+            json_type_name(val),
+            field_type_name(field_type)
+        ))
+    };
     match field_type as u32 {
         pxlib::pxfAlpha => {
-            if let Some(s) = val.as_str() {
-                if let Ok(c_str) = CString::new(s) {
-                    pxlib::PX_put_data_alpha(
-                        pxdoc,
-                        buf_ptr as *mut std::os::raw::c_char,
-                        field_len,
-                        c_str.as_ptr() as *mut std::os::raw::c_char,
-                    );
+            let Some(s) = val.as_str() else {
+                return rejected();
+            };
+            let bytes = match charset::encode(pxdoc, s) {
+                Ok(bytes) => bytes,
+                Err(charset) => {
+                    return Some(format!("{} has characters {} can't store", val, charset))
                 }
-            }
+            };
+            let Ok(c_str) = CString::new(bytes) else {
+                return Some(format!("{} contains a NUL character", val));
+            };
+            pxlib::PX_put_data_alpha(
+                pxdoc,
+                buf_ptr as *mut std::os::raw::c_char,
+                field_len,
+                c_str.as_ptr() as *mut std::os::raw::c_char,
+            );
         }
         pxlib::pxfShort => {
-            if let Some(v) = val.as_i64() {
-                pxlib::PX_put_data_short(
+            let Some(v) = val.as_i64() else {
+                return rejected();
+            };
+            pxlib::PX_put_data_short(
+                pxdoc,
+                buf_ptr as *mut std::os::raw::c_char,
+                field_len,
+                v as std::os::raw::c_short,
+            );
+        }
+        pxlib::pxfLong => {
+            let Some(v) = val.as_i64() else {
+                return rejected();
+            };
+            pxlib::PX_put_data_long(
+                pxdoc,
+                buf_ptr as *mut std::os::raw::c_char,
+                field_len,
+                v as std::os::raw::c_int,
+            );
+        }
+        pxlib::pxfTime => match dates::time_input_ms(val, date_format) {
+            Some(ms) => {
+                pxlib::PX_put_data_long(
                     pxdoc,
                     buf_ptr as *mut std::os::raw::c_char,
                     field_len,
-                    v as std::os::raw::c_short,
+                    ms as std::os::raw::c_int,
                 );
             }
-        }
-        pxlib::pxfLong | pxlib::pxfAutoInc => {
-            if let Some(v) = val.as_i64() {
+            None => return Some(format!("{} is not a TIME (HH:MM:SS)", val)),
+        },
+        pxlib::pxfDate => match dates::date_input_days(val, date_format) {
+            Some(days) => {
                 pxlib::PX_put_data_long(
                     pxdoc,
                     buf_ptr as *mut std::os::raw::c_char,
                     field_len,
-                    v as std::os::raw::c_int,
+                    days as std::os::raw::c_int,
                 );
             }
-        }
-        pxlib::pxfNumber | pxlib::pxfCurrency => {
-            if let Some(v) = val.as_f64() {
+            None => return Some(format!("{} is not a DATE (YYYY-MM-DD)", val)),
+        },
+        pxlib::pxfTimestamp => match dates::timestamp_input_ms(val, date_format) {
+            Some(ms) => {
                 pxlib::PX_put_data_double(
                     pxdoc,
                     buf_ptr as *mut std::os::raw::c_char,
                     field_len,
-                    v,
+                    ms,
                 );
             }
-        }
-        pxlib::pxfLogical => {
-            if let Some(v) = val.as_bool() {
-                pxlib::PX_put_data_byte(
+            None => return Some(format!("{} is not an RFC 3339 TIMESTAMP", val)),
+        },
+        pxlib::pxfBCD => match bcd::from_json(val, field_decimals.max(0) as usize) {
+            Ok(text) => {
+                let Ok(c_str) = CString::new(text) else {
+                    return Some(format!("{} is not a decimal", val));
+                };
+                pxlib::PX_put_data_bcd(
                     pxdoc,
                     buf_ptr as *mut std::os::raw::c_char,
-                    field_len,
-                    if v { 1 } else { 0 },
+                    field_decimals,
+                    c_str.as_ptr() as *mut std::os::raw::c_char,
                 );
             }
+            Err(e) => return Some(e),
+        },
+        pxlib::pxfNumber | pxlib::pxfCurrency => {
+            let Some(v) = val.as_f64() else {
+                return rejected();
+            };
+            pxlib::PX_put_data_double(pxdoc, buf_ptr as *mut std::os::raw::c_char, field_len, v);
+        }
+        pxlib::pxfLogical => {
+            let Some(v) = val.as_bool() else {
+                return rejected();
+            };
+            pxlib::PX_put_data_byte(
+                pxdoc,
+                buf_ptr as *mut std::os::raw::c_char,
+                field_len,
+                if v { 1 } else { 0 },
+            );
+        }
+        // Numbered by `AutoInc` when a record is appended.
+        pxlib::pxfAutoInc => {}
+        _ => {
+            return Some(format!(
+                "{} fields can't be written",
+                field_type_name(field_type)
+            ))
         }
-        _ => {}
     }
+    None
 }
 
-fn compare_values(actual: &Value, query: &Value) -> bool {
-    match (actual, query) {
-        (Value::String(a), Value::String(q)) => a.to_lowercase().contains(&q.to_lowercase()),
-        (Value::Number(a), Value::Number(q)) => a == q,
-        (Value::Bool(a), Value::Bool(q)) => a == q,
-        (Value::String(a), Value::Number(q)) => a == &q.to_string(),
-        _ => actual == query,
+/// Matches decoded records against `search_table`-style queries. `$regex`
+/// patterns are compiled once per call, when the matcher is built.
+struct QueryMatcher {
+    regexes: HashMap<String, Regex>,
+    /// Plain text values match without folding case.
+    case_sensitive: bool,
+}
+
+impl QueryMatcher {
+    /// Compiles every `$regex` pattern in `queries`, rejecting invalid ones
+    /// up front rather than letting them silently match nothing.
+    fn new<'a>(
+        queries: impl IntoIterator<Item = &'a Map<String, Value>>,
+        case_sensitive: bool,
+    ) -> Result<Self, ToolError> {
+        let mut regexes = HashMap::new();
+        for query in queries {
+            let patterns = query
+                .values()
+                .filter_map(|v| v.as_object())
+                .filter_map(|ops| ops.get("$regex"));
+            for pattern in patterns {
+                let Some(pattern) = pattern.as_str() else {
+                    return Err(ToolError::new(
+                        "INVALID_REGEX",
+                        format!("$regex needs a string pattern, got {}.", pattern),
+                    ));
+                };
+                if regexes.contains_key(pattern) {
+                    continue;
+                }
+                let regex = Regex::new(pattern).map_err(|e| {
+                    ToolError::new(
+                        "INVALID_REGEX",
+                        format!("Invalid $regex pattern '{}': {}", pattern, e),
+                    )
+                })?;
+                regexes.insert(pattern.to_string(), regex);
+            }
+        }
+        Ok(QueryMatcher {
+            regexes,
+            case_sensitive,
+        })
+    }
+
+    /// Whether a decoded record satisfies every field of `query`. Query
+    /// fields the table doesn't have are ignored.
+    fn matches(&self, record: &Map<String, Value>, query: &Map<String, Value>) -> bool {
+        query
+            .iter()
+            .all(|(field, query_val)| match record.get(field) {
+                Some(val) => self.compare_values(val, query_val),
+                None => true,
+            })
+    }
+
+    /// Pairs each query field the table has with its position in the
+    /// schema, for `matches_raw`. Fields the table lacks are left out, as
+    /// `matches` ignores them too.
+    fn plan<'q>(
+        &self,
+        fields: &[pxlib::pxfield_t],
+        query: &'q Map<String, Value>,
+    ) -> Vec<(usize, &'q Value)> {
+        query
+            .iter()
+            .filter_map(|(name, query_val)| {
+                fields
+                    .iter()
+                    .position(|f| {
+                        unsafe { std::ffi::CStr::from_ptr(f.px_fname) }.to_string_lossy() == *name
+                    })
+                    .map(|i| (i, query_val))
+            })
+            .collect()
+    }
+
+    /// Same verdict as `matches` on the decoded record, but decodes only
+    /// the planned fields straight from the record buffer, stopping at the
+    /// first that fails, so rejected rows are never fully materialized.
+    unsafe fn matches_raw(
+        &self,
+        pxdoc: *mut pxlib::pxdoc_t,
+        fields: &[pxlib::pxfield_t],
+        offsets: &[usize],
+        buf: &mut [u8],
+        plan: &[(usize, &Value)],
+    ) -> bool {
+        plan.iter().all(|&(i, query_val)| {
+            let f = &fields[i];
+            let val = get_field_value(
+                pxdoc,
+                buf.as_mut_ptr().add(offsets[i]),
+                f.px_ftype,
+                f.px_flen,
+                f.px_fdc,
+            );
+            self.compare_values(&val, query_val)
+        })
+    }
+
+    /// Matches one field against its query value. An object of operators
+    /// (`{"$gt": 100, "$lte": 200}`) must satisfy each of them; any other
+    /// value keeps the plain contains/equals matching.
+    fn compare_values(&self, actual: &Value, query: &Value) -> bool {
+        if let Value::Object(ops) = query {
+            return ops
+                .iter()
+                .all(|(op, operand)| self.matches_operator(actual, op, operand));
+        }
+        match (actual, query) {
+            (Value::String(a), Value::String(q)) if self.case_sensitive => a.contains(q.as_str()),
+            (Value::String(a), Value::String(q)) => a.to_lowercase().contains(&q.to_lowercase()),
+            (Value::Number(a), Value::Number(q)) => a == q,
+            (Value::Bool(a), Value::Bool(q)) => a == q,
+            (Value::String(a), Value::Number(q)) => a == &q.to_string(),
+            _ => actual == query,
+        }
+    }
+
+    /// Applies a single query operator. Numbers compare numerically and
+    /// strings lexicographically (so ISO dates order correctly); blanks and
+    /// mixed types satisfy no ordering. `$regex` tests the field's text, or
+    /// its JSON form for non-text values. Unknown operators never match, so a
+    /// typo filters everything out rather than letting everything through.
+    fn matches_operator(&self, actual: &Value, op: &str, operand: &Value) -> bool {
+        if op == "$regex" {
+            let Some(regex) = operand.as_str().and_then(|p| self.regexes.get(p)) else {
+                return false;
+            };
+            return match actual {
+                Value::Null => false,
+                Value::String(s) => regex.is_match(s),
+                other => regex.is_match(&other.to_string()),
+            };
+        }
+        let ordering = match (actual, operand) {
+            (Value::String(a), Value::String(b)) => Some(a.as_str().cmp(b.as_str())),
+            _ => match (actual.as_f64(), operand.as_f64()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => None,
+            },
+        };
+        match op {
+            "$eq" => ordering.map_or(actual == operand, |o| o.is_eq()),
+            "$ne" => ordering.map_or(actual != operand, |o| o.is_ne()),
+            "$gt" => ordering.is_some_and(|o| o.is_gt()),
+            "$gte" => ordering.is_some_and(|o| o.is_ge()),
+            "$lt" => ordering.is_some_and(|o| o.is_lt()),
+            "$lte" => ordering.is_some_and(|o| o.is_le()),
+            _ => false,
+        }
     }
 }
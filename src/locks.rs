@@ -0,0 +1,65 @@
+use crate::errors::ToolError;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Tables currently held by a tool call, keyed by resolved path.
+static HELD: LazyLock<(Mutex<HashSet<PathBuf>>, Condvar)> =
+    LazyLock::new(|| (Mutex::new(HashSet::new()), Condvar::new()));
+
+/// Exclusive in-process lock on a table file, released on drop.
+pub struct TableLock {
+    path: PathBuf,
+}
+
+impl Drop for TableLock {
+    fn drop(&mut self) {
+        let (held, cvar) = &*HELD;
+        let mut held = held.lock().unwrap_or_else(|e| e.into_inner());
+        held.remove(&self.path);
+        cvar.notify_all();
+    }
+}
+
+/// Whether a tool call currently holds the table at `path`, without waiting.
+pub fn is_held(path: &Path) -> bool {
+    let (held, _) = &*HELD;
+    held.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains(path)
+}
+
+/// Waits up to `timeout_ms` for exclusive access to the table at `path`,
+/// returning a `TABLE_BUSY` error rather than blocking indefinitely. A
+/// timeout too large to represent as a deadline waits without one.
+pub fn acquire(path: &Path, timeout_ms: u64) -> Result<TableLock, ToolError> {
+    let (held, cvar) = &*HELD;
+    let deadline = Instant::now().checked_add(Duration::from_millis(timeout_ms));
+    let mut guard = held.lock().unwrap_or_else(|e| e.into_inner());
+    while guard.contains(path) {
+        let Some(deadline) = deadline else {
+            guard = cvar.wait(guard).unwrap_or_else(|e| e.into_inner());
+            continue;
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(ToolError::new(
+                "TABLE_BUSY",
+                format!(
+                    "Table '{}' is busy (lock not acquired within {} ms); retry later.",
+                    path.display(),
+                    timeout_ms
+                ),
+            ));
+        }
+        guard = cvar
+            .wait_timeout(guard, deadline - now)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+    guard.insert(path.to_path_buf());
+    Ok(TableLock {
+        path: path.to_path_buf(),
+    })
+}
@@ -0,0 +1,45 @@
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Minimal stderr logger; stdout is reserved for JSON-RPC traffic.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Installs the logger, and routes panic messages through it so they honor
+/// the level too.
+pub fn init(level: LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+    std::panic::set_hook(Box::new(|info| log::error!("{}", info)));
+}
+
+/// Maps an MCP log level (RFC 5424 severities) onto the closest `log` filter.
+pub fn level_from_mcp(level: &str) -> Option<LevelFilter> {
+    match level {
+        "debug" => Some(LevelFilter::Debug),
+        "info" | "notice" => Some(LevelFilter::Info),
+        "warning" => Some(LevelFilter::Warn),
+        "error" | "critical" | "alert" | "emergency" => Some(LevelFilter::Error),
+        _ => None,
+    }
+}
+
+/// Reconfigures the active filter at runtime (`logging/setLevel`).
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
@@ -1,50 +1,110 @@
+// The tools/list response is one large json! literal.
+#![recursion_limit = "256"]
+
 mod args;
+mod base64;
+mod bcd;
+mod charset;
+mod dates;
+mod doccache;
+mod errors;
+mod framing;
 mod handlers;
+mod locks;
+mod logging;
 mod mcp;
+mod metrics;
+mod pxdoc;
+mod pxerror;
 mod pxlib;
+mod resources;
+mod rpc;
+mod selftest;
+mod session;
+mod sse;
 
 use args::Args;
-use clap::Parser;
-use mcp::{RpcRequest, RpcResponse};
-use std::io::{self, BufRead, Write};
+use session::ServerState;
+use std::io;
+use std::sync::Arc;
 
 fn main() {
-    let args = Args::parse();
+    let state = ServerState::new(Args::load());
+    let args = &state.args;
+    logging::init(if args.quiet {
+        log::LevelFilter::Error
+    } else {
+        args.log_level
+    });
+    charset::init(args.encoding);
 
     // Minimal initialization of pxlib
     unsafe {
         pxlib::PX_boot();
     }
 
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let reader = stdin.lock();
-
-    for line_result in reader.lines() {
-        if let Ok(line) = line_result {
-            if let Ok(req) = serde_json::from_str::<RpcRequest>(&line) {
-                if let Some(id) = req.id.clone() {
-                    let result = handlers::handle_request(&req, &args);
-                    eprintln!("DEBUG: Handler result for ID {}: {:?}", id, result);
-                    let response = RpcResponse {
-                        jsonrpc: "2.0".to_string(),
-                        id,
-                        result: Some(result),
-                        error: None,
-                    };
-                    if let Ok(json_response) = serde_json::to_string(&response) {
-                        eprintln!("DEBUG: Sending response: {}", json_response);
-                        writeln!(stdout, "{}", json_response).unwrap();
-                        stdout.flush().unwrap();
+    if args.startup_selftest {
+        match selftest::run(args) {
+            Ok(summary) => log::info!("Startup self-test passed: {}", summary),
+            Err(e) => {
+                log::error!("Startup self-test failed: {}", e);
+                if args.selftest_fatal {
+                    unsafe {
+                        pxlib::PX_shutdown();
                     }
+                    std::process::exit(1);
                 }
-            } else {
-                eprintln!("DEBUG: Failed to parse request: {}", line);
             }
         }
     }
 
+    match state.args.port {
+        Some(port) => serve_sse(state, port),
+        None => serve_stdio(&state),
+    }
+
     unsafe {
         pxlib::PX_shutdown();
     }
 }
+
+/// Reads framed messages from stdin until EOF, answering each on stdout.
+fn serve_stdio(state: &ServerState) {
+    let args = &state.args;
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut reader = stdin.lock();
+
+    loop {
+        let message = match framing::read_message(&mut reader, args.framing) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                log::error!("Failed to read from stdin: {}", e);
+                break;
+            }
+        };
+        let reply = if let Some(reason) = message.rejected {
+            rpc::reject_message(reason)
+        } else {
+            rpc::process_message(&message.body, message.truncated, state)
+        };
+        if let Some(reply) = reply {
+            framing::write_message(&mut stdout, args.framing, &reply).unwrap();
+        }
+    }
+}
+
+/// Runs the HTTP/SSE transport until Ctrl-C.
+fn serve_sse(state: ServerState, port: u16) {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::error!("Failed to start the async runtime: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = runtime.block_on(sse::serve(Arc::new(state), port)) {
+        log::error!("SSE transport failed on port {}: {}", port, e);
+    }
+}
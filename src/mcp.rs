@@ -16,5 +16,89 @@ pub struct RpcResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<Value>,
+    pub error: Option<RpcError>,
+}
+
+/// MCP protocol versions this server speaks, newest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
+/// The client's requested version when supported, else our newest.
+pub fn negotiate_protocol_version(requested: Option<&str>) -> &'static str {
+    SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .find(|v| Some(**v) == requested)
+        .unwrap_or(&SUPPORTED_PROTOCOL_VERSIONS[0])
+}
+
+/// JSON-RPC "Parse error" error code.
+pub const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC "Invalid Request" error code.
+pub const INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC "Method not found" error code.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC "Invalid params" error code.
+pub const INVALID_PARAMS: i64 = -32602;
+/// JSON-RPC "Internal error" error code.
+pub const INTERNAL_ERROR: i64 = -32603;
+/// MCP "Resource not found" error code.
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn parse_error(message: impl Into<String>) -> Self {
+        RpcError {
+            code: PARSE_ERROR,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        RpcError {
+            code: INVALID_REQUEST,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Carries the method in `data` too, so an empty or whitespace-only
+    /// name is still visible to the client.
+    pub fn method_not_found(method: &str) -> Self {
+        RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("Method not found: {}", method),
+            data: Some(serde_json::json!({ "method": method })),
+        }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        RpcError {
+            code: INVALID_PARAMS,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        RpcError {
+            code: INTERNAL_ERROR,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn resource_not_found(uri: &str) -> Self {
+        RpcError {
+            code: RESOURCE_NOT_FOUND,
+            message: format!("Resource not found: {}", uri),
+            data: Some(serde_json::json!({ "uri": uri })),
+        }
+    }
 }
@@ -0,0 +1,137 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// Call and error counts for one tool.
+#[derive(Default)]
+struct ToolCounters {
+    calls: u64,
+    errors: u64,
+}
+
+static TOOLS: LazyLock<Mutex<BTreeMap<String, ToolCounters>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+static ROWS_READ: AtomicU64 = AtomicU64::new(0);
+static ROWS_WRITTEN: AtomicU64 = AtomicU64::new(0);
+static HANDLE_CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static HANDLE_CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Counts a finished `tools/call`, as an error when the tool failed.
+pub fn record_call(tool: &str, is_error: bool) {
+    let mut tools = TOOLS.lock().unwrap_or_else(|e| e.into_inner());
+    let counters = tools.entry(tool.to_string()).or_default();
+    counters.calls += 1;
+    if is_error {
+        counters.errors += 1;
+    }
+}
+
+pub fn add_rows_read(rows: usize) {
+    ROWS_READ.fetch_add(rows as u64, Ordering::Relaxed);
+}
+
+pub fn add_rows_written(rows: usize) {
+    ROWS_WRITTEN.fetch_add(rows as u64, Ordering::Relaxed);
+}
+
+/// Counts a `--handle-cache-size` lookup.
+pub fn record_handle_cache(hit: bool) {
+    let counter = if hit {
+        &HANDLE_CACHE_HITS
+    } else {
+        &HANDLE_CACHE_MISSES
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the current counters, zeroing them afterwards when `reset` is set.
+pub fn snapshot(reset: bool) -> Value {
+    let mut tools = TOOLS.lock().unwrap_or_else(|e| e.into_inner());
+    let per_tool: serde_json::Map<String, Value> = tools
+        .iter()
+        .map(|(name, c)| {
+            (
+                name.clone(),
+                json!({ "calls": c.calls, "errors": c.errors }),
+            )
+        })
+        .collect();
+    let read = |counter: &AtomicU64| {
+        if reset {
+            counter.swap(0, Ordering::Relaxed)
+        } else {
+            counter.load(Ordering::Relaxed)
+        }
+    };
+    if reset {
+        tools.clear();
+    }
+    json!({
+        "tools": per_tool,
+        "rowsRead": read(&ROWS_READ),
+        "rowsWritten": read(&ROWS_WRITTEN),
+        "handleCache": {
+            "hits": read(&HANDLE_CACHE_HITS),
+            "misses": read(&HANDLE_CACHE_MISSES)
+        }
+    })
+}
+
+/// Escapes a Prometheus label value: backslash, double quote and newline.
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The counters in the Prometheus text exposition format, for the HTTP
+/// transport's `/metrics` endpoint.
+pub fn prometheus() -> String {
+    let tools = TOOLS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut out = String::new();
+    out.push_str("# HELP paradox_mcp_tool_calls_total Tool calls by tool.\n");
+    out.push_str("# TYPE paradox_mcp_tool_calls_total counter\n");
+    for (name, c) in tools.iter() {
+        out.push_str(&format!(
+            "paradox_mcp_tool_calls_total{{tool=\"{}\"}} {}\n",
+            label_value(name),
+            c.calls
+        ));
+    }
+    out.push_str("# HELP paradox_mcp_tool_errors_total Failed tool calls by tool.\n");
+    out.push_str("# TYPE paradox_mcp_tool_errors_total counter\n");
+    for (name, c) in tools.iter() {
+        out.push_str(&format!(
+            "paradox_mcp_tool_errors_total{{tool=\"{}\"}} {}\n",
+            label_value(name),
+            c.errors
+        ));
+    }
+    out.push_str("# HELP paradox_mcp_rows_read_total Records read from tables.\n");
+    out.push_str("# TYPE paradox_mcp_rows_read_total counter\n");
+    out.push_str(&format!(
+        "paradox_mcp_rows_read_total {}\n",
+        ROWS_READ.load(Ordering::Relaxed)
+    ));
+    out.push_str("# HELP paradox_mcp_rows_written_total Records written to tables.\n");
+    out.push_str("# TYPE paradox_mcp_rows_written_total counter\n");
+    out.push_str(&format!(
+        "paradox_mcp_rows_written_total {}\n",
+        ROWS_WRITTEN.load(Ordering::Relaxed)
+    ));
+    out.push_str(
+        "# HELP paradox_mcp_handle_cache_lookups_total Read handle cache lookups by result.\n",
+    );
+    out.push_str("# TYPE paradox_mcp_handle_cache_lookups_total counter\n");
+    out.push_str(&format!(
+        "paradox_mcp_handle_cache_lookups_total{{result=\"hit\"}} {}\n",
+        HANDLE_CACHE_HITS.load(Ordering::Relaxed)
+    ));
+    out.push_str(&format!(
+        "paradox_mcp_handle_cache_lookups_total{{result=\"miss\"}} {}\n",
+        HANDLE_CACHE_MISSES.load(Ordering::Relaxed)
+    ));
+    out
+}
@@ -0,0 +1,134 @@
+use crate::errors::{invalid_path_error, px_init_error, px_result, PxOp, ToolError};
+use crate::pxerror;
+use crate::pxlib;
+use std::ffi::CString;
+use std::path::Path;
+
+/// Owns a pxlib document. Dropping it closes the table, if one was opened,
+/// and frees the document, so early returns can't leak either.
+pub struct PxDoc {
+    ptr: *mut pxlib::pxdoc_t,
+    opened: bool,
+    layout_warning: Option<String>,
+}
+
+// A handle is only ever used by one thread at a time: the call that opened
+// it, or the one that took it from `doccache`.
+unsafe impl Send for PxDoc {}
+
+impl PxDoc {
+    /// Allocates an empty document that reports pxlib errors through
+    /// `pxerror`.
+    pub fn new() -> Result<Self, ToolError> {
+        let ptr = pxerror::new_doc();
+        if ptr.is_null() {
+            return Err(px_init_error());
+        }
+        Ok(PxDoc {
+            ptr,
+            opened: false,
+            layout_warning: None,
+        })
+    }
+
+    /// Opens the table file at `path` with `PX_open_file` and checks its
+    /// record layout (see `record_layout_warning`). A mismatch is logged, or
+    /// with `strict` (`--strict-types`) fails the open.
+    pub fn open(&mut self, path: &Path, strict: bool) -> Result<(), ToolError> {
+        let path_str = path.to_string_lossy();
+        let c_path = CString::new(path_str.as_ref()).map_err(|_| invalid_path_error())?;
+        px_result(
+            unsafe { pxlib::PX_open_file(self.ptr, c_path.as_ptr()) },
+            PxOp::Open,
+            &path_str,
+        )?;
+        self.opened = true;
+        self.layout_warning = unsafe { record_layout_warning(self.ptr)? };
+        self.check_layout(strict)?;
+        if let Some(warning) = &self.layout_warning {
+            log::warn!("{}: {}", path_str, warning);
+        }
+        Ok(())
+    }
+
+    /// Fails with `RECORD_SIZE_MISMATCH` when `strict` is set and the open
+    /// table's layout is irregular.
+    pub fn check_layout(&self, strict: bool) -> Result<(), ToolError> {
+        match &self.layout_warning {
+            Some(warning) if strict => Err(ToolError::new("RECORD_SIZE_MISMATCH", warning.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Why field values of the open table may be misaligned, if they may.
+    pub fn layout_warning(&self) -> Option<&str> {
+        self.layout_warning.as_deref()
+    }
+
+    /// See `attach_blob_file`.
+    pub fn attach_blob_file(&self, table_path: &Path) {
+        unsafe { attach_blob_file(self.ptr, table_path) }
+    }
+
+    pub fn as_ptr(&self) -> *mut pxlib::pxdoc_t {
+        self.ptr
+    }
+}
+
+/// Compares the header's record size with the summed field lengths, since the
+/// read/write loops address fields by cumulative `px_flen` offsets, and
+/// describes any mismatch. A table without fields is an error: every record
+/// would decode as `{}`.
+unsafe fn record_layout_warning(pxdoc: *mut pxlib::pxdoc_t) -> Result<Option<String>, ToolError> {
+    let record_size = pxlib::PX_get_recordsize(pxdoc);
+    let num_fields = pxlib::PX_get_num_fields(pxdoc);
+    let fields_ptr = pxlib::PX_get_fields(pxdoc);
+    if fields_ptr.is_null() || num_fields <= 0 {
+        return Err(ToolError::new(
+            "NO_FIELDS",
+            "Table has no fields; its header is likely corrupt.",
+        ));
+    }
+    let fields_slice = std::slice::from_raw_parts(fields_ptr, num_fields as usize);
+    let summed: i64 = fields_slice.iter().map(|f| f.px_flen as i64).sum();
+    if summed == record_size as i64 {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "Record size {} does not match the summed field lengths {}; field values may be misaligned.",
+        record_size, summed
+    )))
+}
+
+/// Associates the table's sibling `.mb` blob file, when there is one, so
+/// MEMO fields can be read; without one they decode as null.
+pub unsafe fn attach_blob_file(pxdoc: *mut pxlib::pxdoc_t, table_path: &Path) {
+    let Some(blob_path) = ["mb", "MB"]
+        .iter()
+        .map(|ext| table_path.with_extension(ext))
+        .find(|p| p.exists())
+    else {
+        return;
+    };
+    let Ok(c_path) = CString::new(blob_path.to_string_lossy().as_ref()) else {
+        return;
+    };
+    if pxlib::PX_set_blob_file(pxdoc, c_path.as_ptr()) < 0 {
+        log::warn!(
+            "Failed to open blob file {}: {}",
+            blob_path.display(),
+            pxerror::take_error().unwrap_or_default()
+        );
+    }
+}
+
+impl Drop for PxDoc {
+    fn drop(&mut self) {
+        unsafe {
+            if self.opened {
+                pxlib::PX_close(self.ptr);
+            }
+            pxlib::PX_delete(self.ptr);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::pxlib;
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+
+thread_local! {
+    /// The first diagnostic pxlib reported on this thread since it was last
+    /// taken; later ones are usually consequences of it.
+    static PENDING_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// pxlib error callback: keeps the message for `take_error` instead of
+/// printing it to stderr, as pxlib's default handler does.
+unsafe extern "C" fn capture_error(
+    _pxdoc: *mut pxlib::pxdoc_t,
+    error_type: c_int,
+    msg: *const c_char,
+    _data: *mut c_void,
+) {
+    if msg.is_null() {
+        return;
+    }
+    let msg = CStr::from_ptr(msg).to_string_lossy().trim().to_string();
+    log::debug!("pxlib error (type {}): {}", error_type, msg);
+    PENDING_ERROR.with(|last| {
+        last.borrow_mut().get_or_insert(msg);
+    });
+}
+
+/// `PX_new` with the capturing error handler installed and pxlib's default
+/// allocators. Clears any message left over from an earlier document.
+pub fn new_doc() -> *mut pxlib::pxdoc_t {
+    PENDING_ERROR.with(|last| last.borrow_mut().take());
+    unsafe { pxlib::PX_new2(Some(capture_error), None, None, None) }
+}
+
+/// Takes the pxlib diagnostic captured on this thread, if any.
+pub fn take_error() -> Option<String> {
+    PENDING_ERROR.with(|last| last.borrow_mut().take())
+}
@@ -0,0 +1,202 @@
+use crate::mcp::RpcError;
+use crate::session::ServerState;
+use serde_json::{json, Map, Value};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+pub const RESULT_URI_PREFIX: &str = "paradox://result/";
+pub const TABLE_URI_PREFIX: &str = "paradox://table/";
+
+/// Read-only data tools whose results may be deferred behind a link; the
+/// query is re-run on every read, so write tools must never be linked.
+pub const LINKABLE_TOOLS: &[&str] = &["read_table_data", "read_sorted", "search_table"];
+
+/// A deferred tool result: the query that produced it, re-run on read.
+pub struct LinkEntry {
+    tool: String,
+    arguments: Map<String, Value>,
+    created: Instant,
+}
+
+/// Replaces a successful data tool result with a `resource_link` to a
+/// `paradox://result/<id>` URI that re-runs the same query on
+/// `resources/read`. Errors are passed through unchanged.
+pub fn link_response(
+    tool: &str,
+    arguments: &Map<String, Value>,
+    result: Value,
+    state: &ServerState,
+) -> Value {
+    let args = &state.args;
+    if result.get("isError").and_then(|e| e.as_bool()) == Some(true) {
+        return result;
+    }
+
+    let rows = result
+        .get("content")
+        .and_then(|c| c.as_array())
+        .and_then(|c| c.last())
+        .and_then(|block| block.get("text"))
+        .and_then(|t| t.as_str())
+        .and_then(|t| serde_json::from_str::<Vec<Value>>(t).ok())
+        .map(|rows| rows.len());
+
+    let mut arguments = arguments.clone();
+    arguments.remove("as_resource_link");
+    let table = arguments
+        .get("table_name")
+        .and_then(|t| t.as_str())
+        .unwrap_or("table")
+        .to_string();
+
+    let uri = format!(
+        "{}{}",
+        RESULT_URI_PREFIX,
+        state.session.next_link_id.fetch_add(1, Ordering::Relaxed)
+    );
+    {
+        let ttl = Duration::from_secs(args.resource_link_ttl_secs);
+        let mut links = state
+            .session
+            .links
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        links.retain(|_, entry| entry.created.elapsed() < ttl);
+        links.insert(
+            uri.clone(),
+            LinkEntry {
+                tool: tool.to_string(),
+                arguments,
+                created: Instant::now(),
+            },
+        );
+    }
+
+    let description = match rows {
+        Some(n) => format!("{} data ({} rows)", table, n),
+        None => format!("{} data", table),
+    };
+    json!({
+        "content": [
+            { "type": "text", "text": format!("{} — fetch {} via resources/read (expires in {}s).", description, uri, args.resource_link_ttl_secs) },
+            {
+                "type": "resource_link",
+                "uri": uri,
+                "name": format!("{} {}", tool, table),
+                "description": description,
+                "mimeType": "application/json"
+            }
+        ]
+    })
+}
+
+/// Looks up a live result link, returning the tool name and arguments to
+/// re-run, or a "resource not found" error for unknown or expired URIs.
+pub fn resolve_link(
+    uri: &str,
+    state: &ServerState,
+) -> Result<(String, Map<String, Value>), RpcError> {
+    let ttl = Duration::from_secs(state.args.resource_link_ttl_secs);
+    let mut links = state
+        .session
+        .links
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    links.retain(|_, entry| entry.created.elapsed() < ttl);
+    links
+        .get(uri)
+        .map(|entry| (entry.tool.clone(), entry.arguments.clone()))
+        .ok_or_else(|| RpcError::resource_not_found(uri))
+}
+
+/// A table or record addressed through the `paradox://table/...` templates.
+pub enum TableResource {
+    /// `paradox://table/{name}`: the table's schema and first 100 records.
+    Table(String),
+    /// `paradox://table/{name}/record/{index}`: one physical record.
+    Record(String, i32),
+}
+
+/// The URI templates advertised by `resources/templates/list`.
+pub fn templates() -> Value {
+    json!([
+        {
+            "uriTemplate": format!("{}{{name}}", TABLE_URI_PREFIX),
+            "name": "Paradox table",
+            "description": "Schema and first 100 records of a Paradox table",
+            "mimeType": "application/json"
+        },
+        {
+            "uriTemplate": format!("{}{{name}}/record/{{index}}", TABLE_URI_PREFIX),
+            "name": "Paradox record",
+            "description": "A single record of a Paradox table by its 0-based physical index",
+            "mimeType": "application/json"
+        }
+    ])
+}
+
+/// The `resources/list` entries: one `paradox://table/<name>` resource per
+/// table file.
+pub fn table_resources(tables: &[String]) -> Value {
+    tables
+        .iter()
+        .map(|name| {
+            json!({
+                "uri": format!("{}{}", TABLE_URI_PREFIX, percent_encode(name)),
+                "name": name,
+                "description": format!("Schema and first 100 records of {}", name),
+                "mimeType": "application/json"
+            })
+        })
+        .collect()
+}
+
+/// Parses a URI built from one of the table templates. Returns `None` for
+/// anything else, including names that would escape the location.
+pub fn parse_table_uri(uri: &str) -> Option<TableResource> {
+    let rest = uri.strip_prefix(TABLE_URI_PREFIX)?;
+    let parts: Vec<&str> = rest.split('/').collect();
+    let name = percent_decode(parts[0])?;
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return None;
+    }
+    match parts[1..] {
+        [] => Some(TableResource::Table(name)),
+        ["record", index] => {
+            let index: i32 = index.parse().ok().filter(|i| *i >= 0)?;
+            Some(TableResource::Record(name, index))
+        }
+        _ => None,
+    }
+}
+
+/// Escapes everything but RFC 3986 unreserved characters, the inverse of
+/// `percent_decode`.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Decodes `%XX` escapes that clients apply when expanding a template.
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
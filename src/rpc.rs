@@ -0,0 +1,121 @@
+use crate::errors::ToolError;
+use crate::handlers;
+use crate::mcp::{RpcError, RpcRequest, RpcResponse};
+use crate::session::ServerState;
+use serde_json::Value;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Handles one incoming message (a request, a notification or a batch) and
+/// returns the serialized reply, if one is due. Shared by every transport.
+/// `truncated` marks a message cut off by the client closing its stream.
+pub fn process_message(text: &str, truncated: bool, state: &ServerState) -> Option<String> {
+    log::trace!("Received message: {}", text);
+    let reply = match serde_json::from_str::<Value>(text) {
+        // A batch gets one array of responses; notifications in it add
+        // no entry, and an all-notification batch gets no reply at all.
+        Ok(Value::Array(items)) if items.is_empty() => serde_json::to_string(&error_response(
+            Value::Null,
+            RpcError::invalid_request("Empty batch"),
+        )),
+        Ok(Value::Array(items)) => {
+            let responses: Vec<RpcResponse> = items
+                .into_iter()
+                .filter_map(|item| dispatch(item, state))
+                .collect();
+            if responses.is_empty() {
+                return None;
+            }
+            serde_json::to_string(&responses)
+        }
+        Ok(value) => serde_json::to_string(&dispatch(value, state)?),
+        // The id of unparseable input is unknown, so the error goes out
+        // with a null id rather than leaving the client waiting.
+        Err(e) => {
+            if truncated {
+                log::warn!("Malformed unterminated request at end of input: {}", text);
+            } else {
+                log::warn!("Failed to parse request: {}", text);
+            }
+            serde_json::to_string(&error_response(
+                Value::Null,
+                RpcError::parse_error(format!("Parse error: {}", e)),
+            ))
+        }
+    };
+    let reply = reply.ok()?;
+    log::trace!("Sending response: {}", reply);
+    Some(reply)
+}
+
+/// The reply to input that was refused before parsing; like unparseable
+/// input, it goes out with a null id.
+pub fn reject_message(reason: &str) -> Option<String> {
+    log::warn!("Rejected request: {}", reason);
+    serde_json::to_string(&error_response(Value::Null, RpcError::parse_error(reason))).ok()
+}
+
+/// Runs one request and builds its response; notifications (no id) get
+/// none.
+fn dispatch(message: Value, state: &ServerState) -> Option<RpcResponse> {
+    let req = match serde_json::from_value::<RpcRequest>(message.clone()) {
+        Ok(req) => req,
+        // Well-formed JSON that isn't a valid request, e.g. a missing
+        // method, is answered when it carries an id to echo, or with a null
+        // id when it isn't even an object.
+        Err(e) => {
+            log::trace!("Invalid request: {}", message);
+            let id = match message.get("id") {
+                Some(id) if !id.is_null() => id.clone(),
+                _ if !message.is_object() => Value::Null,
+                _ => return None,
+            };
+            return Some(error_response(
+                id,
+                RpcError::invalid_request(format!("Invalid request: {}", e)),
+            ));
+        }
+    };
+    let Some(id) = req.id.clone() else {
+        if panic::catch_unwind(AssertUnwindSafe(|| {
+            handlers::handle_notification(&req, state)
+        }))
+        .is_err()
+        {
+            log::error!("Notification {} panicked", req.method);
+        }
+        return None;
+    };
+    // pxlib calls on a corrupt table can panic; that fails this request
+    // rather than taking the server down. The hook set in `logging::init`
+    // has already logged the details.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| handlers::handle_request(&req, state)))
+        .unwrap_or_else(|_| {
+            log::error!("Request {} ({}) panicked", id, req.method);
+            let message = "Internal error while handling the request.";
+            if req.method == "tools/call" {
+                Ok(ToolError::new("INTERNAL_ERROR", message).into_value())
+            } else {
+                Err(RpcError::internal(message))
+            }
+        });
+    log::trace!("Handler result for ID {}: {:?}", id, result);
+    let (result, error) = match result {
+        Ok(value) => (Some(value), None),
+        Err(err) => (None, Some(err)),
+    };
+    Some(RpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result,
+        error,
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> RpcResponse {
+    RpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result: None,
+        error: Some(error),
+    }
+}
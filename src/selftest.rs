@@ -0,0 +1,80 @@
+use crate::args::Args;
+use crate::errors::{px_result, PxOp};
+use crate::handlers::{sorted_table_names, table_paths};
+use crate::pxerror;
+use crate::pxlib;
+use std::ffi::CString;
+
+/// Opens the first table in the locations and reads one record from it, to
+/// surface a broken pxlib build or an unreadable location at boot rather
+/// than on the first tool call. Returns a summary on success.
+pub fn run(args: &Args) -> Result<String, String> {
+    for location in &args.location {
+        if let Err(e) = std::fs::read_dir(location) {
+            return Err(format!("Cannot read location '{}': {}", location, e));
+        }
+    }
+    let Some(table_name) = sorted_table_names(&args.location).into_iter().next() else {
+        return Ok(format!(
+            "No tables in '{}'; nothing to read.",
+            args.location.join(", ")
+        ));
+    };
+
+    let Some(full_path) = table_paths(args, &table_name).into_iter().next() else {
+        return Err(format!(
+            "Table '{}' disappeared during the self-test.",
+            table_name
+        ));
+    };
+    let path_str = full_path.to_string_lossy();
+    let c_path = CString::new(path_str.as_ref())
+        .map_err(|_| format!("Invalid table path '{}'.", path_str))?;
+
+    unsafe {
+        let pxdoc = pxerror::new_doc();
+        if pxdoc.is_null() {
+            return Err("Failed to initialize PX library.".to_string());
+        }
+        if let Err(e) = px_result(
+            pxlib::PX_open_file(pxdoc, c_path.as_ptr()),
+            PxOp::Open,
+            &path_str,
+        ) {
+            pxlib::PX_delete(pxdoc);
+            return Err(e.message);
+        }
+
+        let num_records = pxlib::PX_get_num_records(pxdoc);
+        let num_fields = pxlib::PX_get_num_fields(pxdoc);
+        let record_size = pxlib::PX_get_recordsize(pxdoc);
+        let result = if num_fields <= 0 || record_size <= 0 {
+            Err(format!(
+                "Table '{}' reports {} fields and a record size of {}.",
+                table_name, num_fields, record_size
+            ))
+        } else if num_records == 0 {
+            Ok(format!(
+                "Opened '{}' ({} fields, no records).",
+                table_name, num_fields
+            ))
+        } else {
+            let mut buf = vec![0u8; record_size as usize];
+            if pxlib::PX_get_record(pxdoc, 0, buf.as_mut_ptr()).is_null() {
+                Err(format!(
+                    "Failed to read the first record of '{}'.",
+                    table_name
+                ))
+            } else {
+                Ok(format!(
+                    "Opened '{}' and read its first record ({} fields, {} records).",
+                    table_name, num_fields, num_records
+                ))
+            }
+        };
+
+        pxlib::PX_close(pxdoc);
+        pxlib::PX_delete(pxdoc);
+        result
+    }
+}
@@ -0,0 +1,110 @@
+use crate::args::Args;
+use crate::resources::LinkEntry;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// What the client declared in its `initialize` request.
+#[derive(Default, Clone)]
+pub struct ClientInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// The protocol version the client asked for.
+    pub protocol_version: Option<String>,
+    /// The client's `capabilities` object, kept verbatim.
+    pub capabilities: Value,
+}
+
+impl ClientInfo {
+    /// "name version" for logs, or "unknown client" before `initialize`.
+    pub fn label(&self) -> String {
+        match (&self.name, &self.version) {
+            (Some(name), Some(version)) => format!("{} {}", name, version),
+            (Some(name), None) => name.clone(),
+            _ => "unknown client".to_string(),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        json!({
+            "name": self.name,
+            "version": self.version,
+            "protocolVersion": self.protocol_version,
+            "capabilities": self.capabilities
+        })
+    }
+}
+
+/// Mutable per-session state, shared by every request of one client.
+pub struct Session {
+    client: Mutex<ClientInfo>,
+    pub(crate) links: Mutex<HashMap<String, LinkEntry>>,
+    pub(crate) next_link_id: AtomicU64,
+    /// Set once the client sends `notifications/initialized`.
+    initialized: AtomicBool,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            client: Mutex::new(ClientInfo::default()),
+            links: Mutex::new(HashMap::new()),
+            next_link_id: AtomicU64::new(1),
+            initialized: AtomicBool::new(false),
+        }
+    }
+
+    /// Records the `initialize` params, replacing any earlier client.
+    pub fn initialize(&self, params: Option<&Value>) -> ClientInfo {
+        let str_at = |pointer: &str| {
+            params
+                .and_then(|p| p.pointer(pointer))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let info = ClientInfo {
+            name: str_at("/clientInfo/name"),
+            version: str_at("/clientInfo/version"),
+            protocol_version: str_at("/protocolVersion"),
+            capabilities: params
+                .and_then(|p| p.get("capabilities"))
+                .cloned()
+                .unwrap_or_else(|| json!({})),
+        };
+        *self.client.lock().unwrap_or_else(|e| e.into_inner()) = info.clone();
+        info
+    }
+
+    /// Marks the handshake complete; returns false if it already was.
+    pub fn mark_initialized(&self) -> bool {
+        !self.initialized.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::SeqCst)
+    }
+
+    pub fn client(&self) -> ClientInfo {
+        self.client
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Server configuration plus the session state, threaded through every
+/// request in place of the bare `Args`.
+pub struct ServerState {
+    pub args: Args,
+    pub session: Session,
+}
+
+impl ServerState {
+    pub fn new(args: Args) -> Self {
+        ServerState {
+            args,
+            session: Session::new(),
+        }
+    }
+}
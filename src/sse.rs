@@ -0,0 +1,328 @@
+use crate::handlers;
+use crate::metrics;
+use crate::resources::{self, TableResource};
+use crate::rpc;
+use crate::session::ServerState;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// How often an idle event stream sends a comment line, which is also how
+/// a silently vanished client gets noticed.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+/// Largest `POST /messages` body accepted.
+const MAX_BODY: usize = 16 * 1024 * 1024;
+/// CSV rows buffered between the table scan and a slow client.
+const EXPORT_BUFFER_ROWS: usize = 64;
+
+/// An open `GET /sse` stream: where its responses go, and its own session
+/// state so clients don't see each other's `initialize` or result links.
+struct SseSession {
+    sender: mpsc::UnboundedSender<String>,
+    state: Arc<ServerState>,
+}
+
+type Sessions = Arc<Mutex<HashMap<String, SseSession>>>;
+
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+/// Serves the MCP SSE transport on `127.0.0.1:<port>` until Ctrl-C.
+/// Requests must carry a `Host` of `127.0.0.1:<port>` or `localhost:<port>`
+/// and no foreign `Origin`, or get `403 Forbidden`:
+///
+/// - `GET /sse` opens an event stream whose first `endpoint` event names
+///   the URL to post requests to; responses arrive as `message` events.
+/// - `POST /messages?sessionId=<id>` accepts a request, notification or
+///   batch for that stream and answers `202 Accepted`.
+/// - `GET /metrics` returns the counters in Prometheus text format.
+/// - `GET /export?table=<name>` streams a CSV export as `row` events.
+pub async fn serve(state: Arc<ServerState>, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    log::info!("SSE transport listening on http://127.0.0.1:{}/sse", port);
+    let sessions: Sessions = Arc::new(Mutex::new(HashMap::new()));
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        log::warn!("Failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+                let (state, sessions) = (state.clone(), sessions.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, port, state, sessions).await {
+                        log::debug!("Connection from {} ended: {}", peer, e);
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => {
+                log::info!("Shutting down the SSE transport");
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    port: u16,
+    state: Arc<ServerState>,
+    sessions: Sessions,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await? == 0 {
+        return Ok(());
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let (mut host, mut origin) = (None, None);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    if !is_local_request(host.as_deref(), origin.as_deref(), port) {
+        log::warn!(
+            "Refused {} {} with Host {:?} and Origin {:?}",
+            method,
+            target,
+            host,
+            origin
+        );
+        return respond(reader.into_inner(), "403 Forbidden", "Forbidden").await;
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    match (method.as_str(), path) {
+        ("GET", "/sse") => open_stream(reader.into_inner(), &state, sessions).await,
+        ("POST", "/messages") => {
+            if content_length > MAX_BODY {
+                let stream = reader.into_inner();
+                return respond(stream, "413 Payload Too Large", "Request body too large").await;
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).await?;
+            let session_id = query_param(query, "sessionId").unwrap_or_default();
+            post_message(reader.into_inner(), &session_id, body, sessions).await
+        }
+        ("GET", "/metrics") => {
+            let mut stream = reader.into_inner();
+            let body = metrics::prometheus();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await?;
+            stream.write_all(body.as_bytes()).await
+        }
+        ("GET", "/export") => {
+            let table = query_param(query, "table").unwrap_or_default();
+            export_csv(reader.into_inner(), &state, &table).await
+        }
+        _ => respond(reader.into_inner(), "404 Not Found", "Not found").await,
+    }
+}
+
+/// Whether `Host` names this server on the loopback interface and `Origin`,
+/// when a browser sends one, is local too. This keeps a web page whose
+/// domain was rebound to 127.0.0.1 from reaching the server.
+fn is_local_request(host: Option<&str>, origin: Option<&str>, port: u16) -> bool {
+    let local = |authority: &str| {
+        ["127.0.0.1", "localhost"]
+            .iter()
+            .any(|name| authority.eq_ignore_ascii_case(&format!("{}:{}", name, port)))
+    };
+    let local_origin = |origin: &str| {
+        origin
+            .strip_prefix("http://")
+            .or_else(|| origin.strip_prefix("https://"))
+            .is_some_and(local)
+    };
+    host.is_some_and(local) && origin.is_none_or(local_origin)
+}
+
+/// Registers a new session and forwards its responses as `message` events
+/// until the client goes away.
+async fn open_stream(
+    mut stream: TcpStream,
+    state: &ServerState,
+    sessions: Sessions,
+) -> io::Result<()> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let id = format!(
+        "{:x}-{:x}",
+        nanos,
+        NEXT_SESSION.fetch_add(1, Ordering::Relaxed)
+    );
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let session_state = Arc::new(ServerState::new(state.args.clone()));
+    sessions.lock().unwrap_or_else(|e| e.into_inner()).insert(
+        id.clone(),
+        SseSession {
+            sender,
+            state: session_state,
+        },
+    );
+    log::info!("SSE session {} opened", id);
+
+    let result = async {
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n",
+            )
+            .await?;
+        let endpoint = format!("/messages?sessionId={}", id);
+        stream.write_all(sse_event("endpoint", &endpoint).as_bytes()).await?;
+        loop {
+            match tokio::time::timeout(KEEPALIVE, receiver.recv()).await {
+                Ok(Some(reply)) => {
+                    stream.write_all(sse_event("message", &reply).as_bytes()).await?
+                }
+                Ok(None) => return Ok(()),
+                Err(_) => stream.write_all(b": keepalive\n\n").await?,
+            }
+        }
+    }
+    .await;
+
+    sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    log::info!("SSE session {} closed", id);
+    result
+}
+
+/// Accepts a message for a session and runs it off the async threads, as
+/// pxlib calls block; the reply is delivered on the session's stream.
+async fn post_message(
+    stream: TcpStream,
+    session_id: &str,
+    body: Vec<u8>,
+    sessions: Sessions,
+) -> io::Result<()> {
+    let session = sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(session_id)
+        .map(|s| (s.sender.clone(), s.state.clone()));
+    let Some((sender, state)) = session else {
+        return respond(stream, "404 Not Found", "Unknown session").await;
+    };
+    respond(stream, "202 Accepted", "Accepted").await?;
+
+    let text = String::from_utf8_lossy(&body).into_owned();
+    let reply = tokio::task::spawn_blocking(move || rpc::process_message(&text, false, &state))
+        .await
+        .map_err(io::Error::other)?;
+    if let Some(reply) = reply {
+        // The stream may have closed meanwhile; the reply is then dropped.
+        let _ = sender.send(reply);
+    }
+    Ok(())
+}
+
+/// Streams `export_table_csv` output as one `row` event per CSV line while
+/// the table is scanned, then a `done` event with the row count (or an
+/// `error` event). At most `EXPORT_BUFFER_ROWS` rows wait in memory.
+async fn export_csv(mut stream: TcpStream, state: &ServerState, table: &str) -> io::Result<()> {
+    if !state.args.tool_allowed("export_table_csv") {
+        return respond(stream, "403 Forbidden", "export_table_csv is disabled").await;
+    }
+    let uri = format!("{}{}", resources::TABLE_URI_PREFIX, table);
+    let Some(TableResource::Table(table_name)) = resources::parse_table_uri(&uri) else {
+        return respond(stream, "400 Bad Request", "Missing or invalid table").await;
+    };
+
+    let (sender, mut receiver) = mpsc::channel::<String>(EXPORT_BUFFER_ROWS);
+    let args = state.args.clone();
+    let scan = tokio::task::spawn_blocking(move || {
+        handlers::stream_table_csv(&table_name, &args, &mut |line| {
+            // Fails only once the client is gone; the scan then runs out
+            // without sending.
+            let _ = sender.blocking_send(line.to_string());
+        })
+    });
+
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        )
+        .await?;
+    while let Some(line) = receiver.recv().await {
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        stream.write_all(sse_event("row", line).as_bytes()).await?;
+    }
+
+    let outcome = scan.await.map_err(io::Error::other)?;
+    metrics::record_call("export_table_csv", outcome.is_err());
+    let event = match outcome {
+        Ok(rows) => sse_event("done", &serde_json::json!({ "rows": rows }).to_string()),
+        Err(e) => sse_event("error", &e.into_value().to_string()),
+    };
+    stream.write_all(event.as_bytes()).await
+}
+
+/// Formats one SSE event; multi-line data becomes several `data:` lines,
+/// which clients join back with newlines.
+fn sse_event(event: &str, data: &str) -> String {
+    let mut out = format!("event: {}\n", event);
+    for line in data.split('\n') {
+        out.push_str("data: ");
+        out.push_str(line.strip_suffix('\r').unwrap_or(line));
+        out.push('\n');
+    }
+    out.push('\n');
+    out
+}
+
+/// Writes a short plain-text response and closes the connection.
+async fn respond(mut stream: TcpStream, status: &str, body: &str) -> io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
+/// The raw (still percent-encoded) value of `key` in a query string.
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v.to_string())
+}
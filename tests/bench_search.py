@@ -0,0 +1,55 @@
+import json
+import os
+import subprocess
+import sys
+import time
+
+ROWS = 50_000
+FIELDS = 30
+RUNS = 5
+
+def send_request(proc, method, params=None):
+    proc.stdin.write(json.dumps({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}) + "\n")
+    proc.stdin.flush()
+    return json.loads(proc.stdout.readline())
+
+def bench_search():
+    """Times a selective search_table query over a wide 50k-row table."""
+    location = "/tmp/paradox_bench"
+    os.makedirs(location, exist_ok=True)
+    binary = sys.argv[1] if len(sys.argv) > 1 else "paradox-mcp"
+    proc = subprocess.Popen(
+        [binary, "--location", location, "--permit-editing"],
+        stdin=subprocess.PIPE,
+        stdout=subprocess.PIPE,
+        stderr=subprocess.DEVNULL,
+        text=True
+    )
+    try:
+        send_request(proc, "initialize", {"protocolVersion": "2024-11-05", "capabilities": {}, "clientInfo": {"name": "bench", "version": "1.0"}})
+        fields = [{"name": "ID", "type": "LONG"}] + [{"name": f"Col{i:02d}", "type": "ALPHA", "length": 20} for i in range(FIELDS - 1)]
+        send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "wide", "fields": fields, "overwrite": True}})
+
+        header = ",".join(f["name"] for f in fields)
+        rows = [",".join([str(i)] + [f"value_{i % 97}_{c}" for c in range(FIELDS - 1)]) for i in range(ROWS)]
+        res = send_request(proc, "tools/call", {"name": "import_csv", "arguments": {"table_name": "wide", "csv_text": header + "\n" + "\n".join(rows)}})
+        print(res["result"]["content"][0]["text"])
+
+        # Matches about 1% of rows, so nearly every record is rejected.
+        query = {"ID": {"$gte": 0}, "Col00": "value_42_0"}
+        timings = []
+        for _ in range(RUNS):
+            start = time.perf_counter()
+            res = send_request(proc, "tools/call", {"name": "search_table", "arguments": {"table_name": "wide", "query": query, "limit": ROWS}})
+            timings.append(time.perf_counter() - start)
+        found = len(json.loads(res["result"]["content"][-1]["text"]))
+        print(f"search_table over {ROWS} rows x {FIELDS} fields: {found} matches, "
+              f"best {min(timings) * 1000:.1f} ms, median {sorted(timings)[RUNS // 2] * 1000:.1f} ms")
+    finally:
+        proc.terminate()
+        for f in os.listdir(location):
+            os.remove(os.path.join(location, f))
+        os.rmdir(location)
+
+if __name__ == "__main__":
+    bench_search()
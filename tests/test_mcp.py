@@ -3,12 +3,13 @@ import subprocess
 import sys
 import time
 import os
+import re
 import threading
 
-def send_request(proc, method, params=None):
+def send_request(proc, method, params=None, req_id=1):
     req = {
         "jsonrpc": "2.0",
-        "id": 1,
+        "id": req_id,
         "method": method,
         "params": params
     }
@@ -21,6 +22,13 @@ def send_request(proc, method, params=None):
     print(f"DEBUG Response: {res}")
     return res
 
+def rss_kb(proc):
+    with open(f"/proc/{proc.pid}/status") as f:
+        for line in f:
+            if line.startswith("VmRSS:"):
+                return int(line.split()[1])
+    return 0
+
 def log_stderr(proc):
     for line in proc.stderr:
         print(f"SERVER STDERR: {line.strip()}")
@@ -43,6 +51,73 @@ def test_mcp_lifecycle():
     stderr_thread.start()
 
     try:
+        print("Testing ping...")
+        res = send_request(proc, "ping", req_id=42)
+        assert res["id"] == 42
+        assert res["result"] == {}
+
+        print("Testing initialize...")
+        res = send_request(proc, "initialize", {"protocolVersion": "2024-11-05", "capabilities": {}})
+        cargo = open(os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "Cargo.toml")).read()
+        version = re.search(r'^version = "([^"]+)"', cargo, re.M).group(1)
+        assert res["result"]["serverInfo"] == {"name": "paradox-mcp-rust", "version": version}
+
+        print("Testing malformed JSON...")
+        proc.stdin.write("{not json\n")
+        proc.stdin.flush()
+        res = json.loads(proc.stdout.readline())
+        assert res["id"] is None
+        assert res["error"]["code"] == -32700
+        # A line that isn't UTF-8 is refused without ending the session.
+        proc.stdin.buffer.write(b'{"jsonrpc": "2.0", "id": 7, "method": "\xff"}\n')
+        proc.stdin.buffer.flush()
+        res = json.loads(proc.stdout.readline())
+        assert res["id"] is None
+        assert res["error"]["code"] == -32700
+        assert send_request(proc, "ping", req_id=44)["id"] == 44
+
+        print("Testing batches...")
+        def send_raw(text):
+            proc.stdin.write(text + "\n")
+            proc.stdin.flush()
+        batch = [
+            {"jsonrpc": "2.0", "id": 1, "method": "ping"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "id": 2, "method": "no/such/method"},
+        ]
+        send_raw(json.dumps(batch))
+        replies = json.loads(proc.stdout.readline())
+        assert [r["id"] for r in replies] == [1, 2]
+        assert replies[0]["result"] == {}
+        assert replies[1]["error"]["code"] == -32601
+        send_raw(json.dumps([{"jsonrpc": "2.0", "method": "notifications/initialized"}] * 2))
+        res = send_request(proc, "ping", req_id=43)
+        assert res["id"] == 43
+        send_raw("[]")
+        res = json.loads(proc.stdout.readline())
+        assert res["id"] is None
+        assert res["error"]["code"] == -32600
+
+        print("Testing logging/setLevel...")
+        res = send_request(proc, "logging/setLevel", {"level": "warning"})
+        assert res["result"] == {}
+        for params in [{"level": "loud"}, {}]:
+            res = send_request(proc, "logging/setLevel", params)
+            assert res["error"]["code"] == -32602
+
+        print("Testing unknown methods...")
+        for method in ["no/such/method", ""]:
+            res = send_request(proc, method)
+            assert res["error"]["code"] == -32601
+            assert res["error"]["message"] == f"Method not found: {method}"
+
+        print("Testing metrics for unknown tools...")
+        send_request(proc, "metrics", {"reset": True})
+        res = send_request(proc, "tools/call", {"name": "no_such_tool\"}\n", "arguments": {}})
+        assert res["result"]["isError"] is True
+        res = send_request(proc, "metrics")
+        assert res["result"]["tools"] == {"unknown": {"calls": 1, "errors": 1}}
+
         print("Testing tools/list...")
         res = send_request(proc, "tools/list")
         tools = [t["name"] for t in res["result"]["tools"]]
@@ -64,6 +139,35 @@ def test_mcp_lifecycle():
         res = send_request(proc, "tools/call", {"name": "list_tables"})
         assert table_name in res["result"]["content"][0]["text"]
 
+        res = send_request(proc, "tools/call", {"name": "list_tables", "arguments": {"detailed": True}})
+        details = json.loads(res["result"]["content"][1]["text"])
+        assert details[0]["name"] == table_name + ".db"
+        assert details[0]["records"] == 0 and details[0]["bytes"] > 0
+
+        print("Testing table_exists...")
+        res = send_request(proc, "tools/call", {"name": "table_exists", "arguments": {"table_name": table_name}})
+        assert json.loads(res["result"]["content"][1]["text"])["exists"] is True
+        res = send_request(proc, "tools/call", {"name": "table_exists", "arguments": {"table_name": "no_such_table"}})
+        assert json.loads(res["result"]["content"][1]["text"])["exists"] is False
+        res = send_request(proc, "tools/call", {"name": "table_exists", "arguments": {"table_name": "../etc/passwd"}})
+        assert res["result"].get("isError") is True
+        res = send_request(proc, "tools/call", {"name": "table_exists", "arguments": {"table_index": 0}})
+        assert json.loads(res["result"]["content"][1]["text"])["exists"] is True
+        res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_index": 0}})
+        assert json.loads(res["result"]["content"][1]["text"]) == {"count": 0}
+        for index in [-1, "0", 1.5]:
+            res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_index": index}})
+            assert res["error"]["code"] == -32602, index
+        res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_index": 9999}})
+        assert res["result"]["_meta"]["errorCode"] == "TABLE_INDEX_OUT_OF_RANGE"
+
+        print("Testing get_server_status...")
+        res = send_request(proc, "tools/call", {"name": "get_server_status"})
+        status = json.loads(res["result"]["content"][1]["text"])
+        assert status["locations"] == [location]
+        assert status["permit_editing"] is True
+        assert status["table_count"] == 1
+
         print("Testing insert_record...")
         record = {"ID": 1, "Name": "Alice", "Active": True}
         res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": table_name, "record": record}})
@@ -81,6 +185,56 @@ def test_mcp_lifecycle():
         assert len(search_results) == 1
         assert search_results[0]["Name"].strip() == "Alice"
 
+        print("Testing search_table operators...")
+        res = send_request(proc, "tools/call", {"name": "search_table", "arguments": {"table_name": table_name, "query": {"ID": {"$gte": 1, "$lt": 2}}}})
+        assert len(json.loads(res["result"]["content"][1]["text"])) == 1
+        for query in [{"ID": {"$gt": 1}}, {"ID": {"$bogus": 1}}]:
+            res = send_request(proc, "tools/call", {"name": "search_table", "arguments": {"table_name": table_name, "query": query}})
+            assert json.loads(res["result"]["content"][1]["text"]) == []
+
+        print("Testing search_table regex...")
+        res = send_request(proc, "tools/call", {"name": "search_table", "arguments": {"table_name": table_name, "query": {"Name": {"$regex": "^Ali"}}}})
+        assert len(json.loads(res["result"]["content"][1]["text"])) == 1
+        res = send_request(proc, "tools/call", {"name": "search_table", "arguments": {"table_name": table_name, "query": {"Name": {"$regex": "^lice"}}}})
+        assert json.loads(res["result"]["content"][1]["text"]) == []
+        res = send_request(proc, "tools/call", {"name": "search_table", "arguments": {"table_name": table_name, "query": {"Name": {"$regex": "("}}}})
+        assert res["result"]["_meta"]["errorCode"] == "INVALID_REGEX"
+
+        print("Testing search_table case sensitivity...")
+        for case_sensitive, expected in [(False, 1), (True, 0)]:
+            args = {"table_name": table_name, "query": {"Name": "ALICE"}, "case_sensitive": case_sensitive}
+            res = send_request(proc, "tools/call", {"name": "search_table", "arguments": args})
+            assert len(json.loads(res["result"]["content"][1]["text"])) == expected
+
+        print("Testing inspect_value...")
+        res = send_request(proc, "tools/call", {"name": "inspect_value", "arguments": {"table_name": table_name, "field": "Name", "value": ""}})
+        report = json.loads(res["result"]["content"][1]["text"])
+        assert not any(w.startswith("Nothing was stored") for w in report["warnings"])
+        res = send_request(proc, "tools/call", {"name": "inspect_value", "arguments": {"table_name": table_name, "field": "Name", "value": 5}})
+        report = json.loads(res["result"]["content"][1]["text"])
+        assert report["warnings"] == ["Nothing was stored: a JSON number is not accepted for ALPHA fields."]
+
+        print("Testing distinct_values...")
+        res = send_request(proc, "tools/call", {"name": "distinct_values", "arguments": {"table_name": table_name, "field_name": "Active"}})
+        assert json.loads(res["result"]["content"][1]["text"]) == [{"value": True, "count": 1}]
+        res = send_request(proc, "tools/call", {"name": "distinct_values", "arguments": {"table_name": table_name, "field_name": "Nope"}})
+        assert res["result"]["_meta"]["errorCode"] == "FIELD_NOT_FOUND"
+
+        print("Testing aggregate...")
+        args = {"table_name": table_name, "field": "ID", "op": "sum", "query": {"Name": "Ali"}}
+        res = send_request(proc, "tools/call", {"name": "aggregate", "arguments": args})
+        assert json.loads(res["result"]["content"][1]["text"]) == 1
+        args = {"table_name": table_name, "field": "Name", "op": "avg"}
+        res = send_request(proc, "tools/call", {"name": "aggregate", "arguments": args})
+        assert res["result"]["_meta"]["errorCode"] == "AGGREGATE_TYPE_MISMATCH"
+
+        print("Testing integer argument validation...")
+        for call in [{"name": "update_record", "arguments": {"table_name": table_name, "index": -1, "record": {"Name": "X"}}},
+                     {"name": "read_table_data", "arguments": {"table_name": table_name, "limit": 1.5}},
+                     {"name": "read_table_data", "arguments": {"table_name": table_name, "offset": "5"}}]:
+            res = send_request(proc, "tools/call", call)
+            assert res["error"]["code"] == -32602, call
+
         print("Testing update_record...")
         update_data = {"Name": "Alicia"}
         res = send_request(proc, "tools/call", {"name": "update_record", "arguments": {"table_name": table_name, "index": 0, "record": update_data}})
@@ -91,6 +245,281 @@ def test_mcp_lifecycle():
         records = json.loads(res["result"]["content"][1]["text"])
         assert len(records) == 1
         assert records[0]["Name"].strip() == "Alicia"
+        for offset in [2**64 - 1, 25, -1]:
+            res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": table_name, "field_offsets": {"Name": offset}}})
+            assert res["result"]["_meta"]["errorCode"] == "INVALID_FIELD_OFFSET", offset
+
+        print("Testing get_record...")
+        res = send_request(proc, "tools/call", {"name": "get_record", "arguments": {"table_name": table_name, "index": 0}})
+        assert json.loads(res["result"]["content"][1]["text"])["Name"].strip() == "Alicia"
+        res = send_request(proc, "tools/call", {"name": "get_record", "arguments": {"table_name": table_name, "index": 5}})
+        assert res["result"]["_meta"]["errorCode"] == "INDEX_OUT_OF_RANGE"
+
+        print("Testing count_records...")
+        res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_name": table_name}})
+        assert json.loads(res["result"]["content"][1]["text"]) == {"count": 1}
+
+        print("Testing copy_table...")
+        copy_args = {"source": table_name, "destination": "test_table_copy"}
+        res = send_request(proc, "tools/call", {"name": "copy_table", "arguments": copy_args})
+        assert res["result"]["_meta"]["records"] == 1
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "test_table_copy"}})
+        assert json.loads(res["result"]["content"][1]["text"])[0]["Name"].strip() == "Alicia"
+        res = send_request(proc, "tools/call", {"name": "copy_table", "arguments": copy_args})
+        assert res["result"]["_meta"]["errorCode"] == "TABLE_EXISTS"
+        res = send_request(proc, "tools/call", {"name": "copy_table", "arguments": {**copy_args, "overwrite": True}})
+        assert "backed up" in res["result"]["content"][0]["text"]
+
+        print("Testing drop_table...")
+        drop_args = {"table_name": "test_table_copy"}
+        res = send_request(proc, "tools/call", {"name": "drop_table", "arguments": drop_args})
+        assert res["result"]["_meta"]["errorCode"] == "CONFIRMATION_REQUIRED"
+        res = send_request(proc, "tools/call", {"name": "drop_table", "arguments": {**drop_args, "confirm": True}})
+        assert res["result"]["_meta"]["removed"] == [os.path.join(location, "test_table_copy.db")]
+        res = send_request(proc, "tools/call", {"name": "table_exists", "arguments": drop_args})
+        assert json.loads(res["result"]["content"][1]["text"])["exists"] is False
+
+        print("Testing case-insensitive table names...")
+        send_request(proc, "tools/call", {"name": "copy_table", "arguments": {"source": table_name, "destination": "UPPER.DB"}})
+        res = send_request(proc, "tools/call", {"name": "list_tables"})
+        assert "UPPER.DB" in res["result"]["content"][0]["text"]
+        res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_name": "upper"}})
+        assert json.loads(res["result"]["content"][1]["text"]) == {"count": 1}
+        send_request(proc, "tools/call", {"name": "drop_table", "arguments": {"table_name": "upper", "confirm": True}})
+        assert not os.path.exists(os.path.join(location, "UPPER.DB"))
+
+        print("Testing blank fields...")
+        blank_fields = [{"name": "Qty", "type": "SHORT"}, {"name": "Total", "type": "NUMBER"}, {"name": "Paid", "type": "LOGICAL"}]
+        send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "blanks", "fields": blank_fields}})
+        send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "blanks", "records": [{}, {"Qty": 0, "Total": 0, "Paid": False}]}})
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "blanks"}})
+        assert json.loads(res["result"]["content"][1]["text"]) == [
+            {"Qty": None, "Total": None, "Paid": None},
+            {"Qty": 0, "Total": 0.0, "Paid": False},
+        ]
+        send_request(proc, "tools/call", {"name": "drop_table", "arguments": {"table_name": "blanks", "confirm": True}})
+
+        print("Testing create_table with a primary key...")
+        key_fields = [{"name": "Code", "type": "ALPHA", "length": 5}, {"name": "Label", "type": "ALPHA", "length": 20}]
+        res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "keyed", "fields": key_fields, "primary_key_fields": 3}})
+        assert res["result"]["_meta"]["errorCode"] == "INVALID_PRIMARY_KEY"
+        res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "keyed", "fields": key_fields, "primary_key_fields": 1}})
+        assert "Successfully created" in res["result"]["content"][0]["text"]
+        assert os.path.exists(os.path.join(location, "keyed.px"))
+        rows = [{"Code": c, "Label": c.lower()} for c in ["A1", "B2", "C3"]]
+        send_request(proc, "tools/call", {"name": "bulk_insert", "arguments": {"table_name": "keyed", "records": rows}})
+        send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "keyed", "record": {"Code": "D4", "Label": "d4"}}})
+        res = send_request(proc, "tools/call", {"name": "read_sorted", "arguments": {"table_name": "keyed"}})
+        assert "warnings" not in res["result"].get("_meta", {})
+        assert [r["Code"].strip() for r in json.loads(res["result"]["content"][1]["text"])] == ["A1", "B2", "C3", "D4"]
+        send_request(proc, "tools/call", {"name": "delete_record", "arguments": {"table_name": "keyed", "index": 0}})
+        res = send_request(proc, "tools/call", {"name": "read_sorted", "arguments": {"table_name": "keyed"}})
+        assert [r["Code"].strip() for r in json.loads(res["result"]["content"][1]["text"])] == ["B2", "C3", "D4"]
+
+        print("Testing key order on out-of-order inserts...")
+        rows = [{"Code": c, "Label": c.lower()} for c in ["Z9", "A0", "M5"]]
+        send_request(proc, "tools/call", {"name": "bulk_insert", "arguments": {"table_name": "keyed", "records": rows}})
+        res = send_request(proc, "tools/call", {"name": "read_sorted", "arguments": {"table_name": "keyed", "offset": 1, "limit": 3}})
+        assert [r["Code"].strip() for r in json.loads(res["result"]["content"][1]["text"])] == ["B2", "C3", "D4"]
+        res = send_request(proc, "tools/call", {"name": "key_range", "arguments": {"table_name": "keyed", "from_key": "C", "to_key": "N"}})
+        assert [r["Code"].strip() for r in json.loads(res["result"]["content"][1]["text"])] == ["C3", "D4", "M5"]
+        res = send_request(proc, "tools/call", {"name": "read_sorted", "arguments": {"table_name": table_name}})
+        assert res["result"]["_meta"]["warnings"]
+        res = send_request(proc, "tools/call", {"name": "key_range", "arguments": {"table_name": table_name, "field": "Name", "from_key": "A", "to_key": "B"}})
+        assert res["result"]["_meta"]["warnings"]
+        assert [r["Name"].strip() for r in json.loads(res["result"]["content"][1]["text"])] == ["Alicia"]
+        res = send_request(proc, "tools/call", {"name": "key_range", "arguments": {"table_name": table_name, "field": "Nope", "from_key": 0, "to_key": 1}})
+        assert res["result"]["_meta"]["errorCode"] == "FIELD_NOT_FOUND"
+        send_request(proc, "tools/call", {"name": "drop_table", "arguments": {"table_name": "keyed", "confirm": True}})
+
+        print("Testing create_table overwrite backups...")
+        dup_fields = [{"name": "Word", "type": "ALPHA", "length": 10}]
+        send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "dup", "fields": dup_fields}})
+        send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "dup", "record": {"Word": "kept"}}})
+        with open(os.path.join(location, "dup.db"), "rb") as f:
+            old_data = f.read()
+        res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "dup", "fields": dup_fields}})
+        assert res["result"]["_meta"]["errorCode"] == "TABLE_EXISTS"
+        res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "dup", "fields": dup_fields, "overwrite": True}})
+        assert "Successfully created" in res["result"]["content"][0]["text"]
+        backups = [f for f in os.listdir(location) if f.startswith("dup.db.") and f.endswith(".bak")]
+        assert len(backups) == 1
+        with open(os.path.join(location, backups[0]), "rb") as f:
+            assert f.read() == old_data
+        res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_name": "dup"}})
+        assert json.loads(res["result"]["content"][1]["text"])["count"] == 0
+        send_request(proc, "tools/call", {"name": "drop_table", "arguments": {"table_name": "dup", "confirm": True}})
+        os.remove(os.path.join(location, backups[0]))
+
+        print("Testing get_table_info...")
+        res = send_request(proc, "tools/call", {"name": "get_table_info", "arguments": {"table_name": table_name}})
+        info = json.loads(res["result"]["content"][1]["text"])
+        assert (info["records"], info["fields"], info["record_size"]) == (1, 3, 25)
+        assert info["db"]["bytes"] > 0
+        assert info["layout_warning"] is None
+
+        print("Testing export_table_csv to a file...")
+        res = send_request(proc, "tools/call", {"name": "export_table_csv", "arguments": {"table_name": table_name, "output_path": "export.csv"}})
+        assert "Successfully exported 1 records" in res["result"]["content"][0]["text"]
+        with open(os.path.join(location, "export.csv")) as f:
+            assert f.readline().strip() == "ID,Name,Active"
+        res = send_request(proc, "tools/call", {"name": "export_table_csv", "arguments": {"table_name": table_name, "output_path": "export.csv"}})
+        assert res["result"]["_meta"]["errorCode"] == "OUTPUT_EXISTS"
+        res = send_request(proc, "tools/call", {"name": "export_table_csv", "arguments": {"table_name": table_name, "output_path": "export.csv", "overwrite": True}})
+        assert "Successfully exported 1 records" in res["result"]["content"][0]["text"]
+        for path in ["/tmp/paradox_escape.csv", "../paradox_escape.csv"]:
+            res = send_request(proc, "tools/call", {"name": "export_table_csv", "arguments": {"table_name": table_name, "output_path": path}})
+            assert res["result"]["_meta"]["errorCode"] == "INVALID_OUTPUT_PATH", path
+        os.remove(os.path.join(location, "export.csv"))
+
+        print("Testing import_csv...")
+        csv_text = 'ID,Name,Active\n2,"Smith, Bob",yes\nthree,Carol,no\n4,Dan,\n'
+        res = send_request(proc, "tools/call", {"name": "import_csv", "arguments": {"table_name": table_name, "csv_text": csv_text}})
+        summary = json.loads(res["result"]["content"][1]["text"])
+        assert summary["inserted"] == 2
+        assert [s["line"] for s in summary["skipped"]] == [3]
+        for path in ["/etc/passwd", "../../etc/passwd"]:
+            res = send_request(proc, "tools/call", {"name": "import_csv", "arguments": {"table_name": table_name, "input_path": path}})
+            assert res["result"]["_meta"]["errorCode"] == "INVALID_INPUT_PATH", path
+
+        print("Testing path traversal guard...")
+        for name in ["../outside", "../../etc/passwd", "/etc/passwd", "sub/../../outside", "secret.conf"]:
+            res = send_request(proc, "tools/call", {"name": "read_table_schema", "arguments": {"table_name": name}})
+            assert res["result"]["_meta"]["errorCode"] == "INVALID_TABLE_NAME", name
+
+        print("Testing resources/read on a record template...")
+        res = send_request(proc, "resources/read", {"uri": f"paradox://table/{table_name}/record/0"})
+        record = json.loads(res["result"]["contents"][0]["text"])
+        assert record["Name"].strip() == "Alicia"
+        res = send_request(proc, "tools/call", {"name": "get_record", "arguments": {"table_name": table_name, "index": 0}})
+        assert json.loads(res["result"]["content"][1]["text"]) == record
+
+        print("Testing TIME decoding...")
+        time_fields = [{"name": "ID", "type": "LONG"}, {"name": "At", "type": "TIME"}]
+        res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "times", "fields": time_fields}})
+        assert "Successfully created" in res["result"]["content"][0]["text"]
+        for rec in [{"ID": 1, "At": "08:15:30"}, {"ID": 2, "At": "23:59:59.250"}, {"ID": 3}]:
+            res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "times", "record": rec}})
+            assert "Successfully inserted" in res["result"]["content"][0]["text"]
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "times"}})
+        times = {r["ID"]: r["At"] for r in json.loads(res["result"]["content"][1]["text"])}
+        assert times == {1: "08:15:30.000", 2: "23:59:59.250", 3: None}
+
+        print("Testing read_table_data pagination...")
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "times", "offset": 1, "limit": 1}})
+        assert [r["ID"] for r in json.loads(res["result"]["content"][1]["text"])] == [2]
+        assert res["result"]["_meta"]["nextOffset"] == 2
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "times", "offset": 2}})
+        assert [r["ID"] for r in json.loads(res["result"]["content"][1]["text"])] == [3]
+        assert "nextOffset" not in res["result"]["_meta"]
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "times", "offset": 10}})
+        assert "isError" not in res["result"]
+        assert json.loads(res["result"]["content"][1]["text"]) == []
+
+        print("Testing search_table paging...")
+        args = {"table_name": "times", "query": {"ID": {"$gte": 1}}, "offset": 1, "limit": 1}
+        res = send_request(proc, "tools/call", {"name": "search_table", "arguments": args})
+        assert [r["ID"] for r in json.loads(res["result"]["content"][1]["text"])] == [2]
+        assert res["result"]["_meta"]["limitReached"] is True
+        assert res["result"]["_meta"]["nextOffset"] == 2
+        args["offset"] = 2
+        res = send_request(proc, "tools/call", {"name": "search_table", "arguments": args})
+        assert [r["ID"] for r in json.loads(res["result"]["content"][1]["text"])] == [3]
+        assert res["result"]["_meta"]["limitReached"] is False
+
+        print("Testing delete_record...")
+        res = send_request(proc, "tools/call", {"name": "delete_record", "arguments": {"table_name": "times", "index": 0}})
+        assert "Successfully deleted record 0" in res["result"]["content"][0]["text"]
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "times"}})
+        assert [r["ID"] for r in json.loads(res["result"]["content"][1]["text"])] == [2, 3]
+        res = send_request(proc, "tools/call", {"name": "delete_record", "arguments": {"table_name": "times", "index": 5}})
+        assert res["result"]["isError"] is True
+        assert res["result"]["_meta"]["errorCode"] == "INDEX_OUT_OF_RANGE"
+
+        print("Testing insert_record with records...")
+        records = [{"ID": 4, "At": "10:00:00"}, "not a record", {"ID": 5, "At": "11:00:00"}]
+        res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "times", "records": records}})
+        results = json.loads(res["result"]["content"][-1]["text"])
+        assert [r.get("index") for r in results] == [2, None, 3]
+        assert results[1]["error"] == "Record is not an object"
+
+        print("Testing update_by_query and delete_by_query...")
+        args = {"table_name": "times", "query": {"ID": {"$gte": 3}}, "record": {"At": "12:00:00"}, "max_affected": 2}
+        res = send_request(proc, "tools/call", {"name": "update_by_query", "arguments": args})
+        assert res["result"]["_meta"]["errorCode"] == "TOO_MANY_MATCHES"
+        args["max_affected"] = 3
+        res = send_request(proc, "tools/call", {"name": "update_by_query", "arguments": args})
+        assert res["result"]["_meta"]["affected"] == 3
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "times"}})
+        times = {r["ID"]: r["At"] for r in json.loads(res["result"]["content"][1]["text"])}
+        assert times == {2: "23:59:59.250", 3: "12:00:00.000", 4: "12:00:00.000", 5: "12:00:00.000"}
+        # A stray record must not turn the delete into an update.
+        res = send_request(proc, "tools/call", {"name": "delete_by_query", "arguments": {"table_name": "times", "query": {"ID": {"$ne": 4}}, "record": {"At": "01:00:00"}}})
+        assert res["result"]["_meta"]["indices"] == [0, 1, 3]
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "times"}})
+        assert [r["ID"] for r in json.loads(res["result"]["content"][1]["text"])] == [4]
+
+        print("Testing truncate_table...")
+        res = send_request(proc, "tools/call", {"name": "truncate_table", "arguments": {"table_name": "times"}})
+        assert res["result"]["_meta"]["errorCode"] == "CONFIRMATION_REQUIRED"
+        for _ in range(2):
+            res = send_request(proc, "tools/call", {"name": "truncate_table", "arguments": {"table_name": "times", "confirm": True}})
+            assert "isError" not in res["result"]
+        assert res["result"]["_meta"]["removed"] == 0
+        res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_name": "times"}})
+        assert json.loads(res["result"]["content"][-1]["text"])["count"] == 0
+
+        print("Testing DATE and TIMESTAMP writes...")
+        date_fields = [{"name": "ID", "type": "LONG"}, {"name": "Day", "type": "DATE"}, {"name": "Stamp", "type": "TIMESTAMP"}]
+        res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "dates", "fields": date_fields}})
+        assert "Successfully created" in res["result"]["content"][0]["text"]
+        record = {"ID": 1, "Day": "2024-02-29", "Stamp": "2023-04-01T15:45:00+02:00"}
+        res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "dates", "record": record}})
+        assert "warnings" not in res["result"].get("_meta", {})
+        res = send_request(proc, "tools/call", {"name": "update_record", "arguments": {"table_name": "dates", "index": 0, "record": {"Day": "2023-02-29"}}})
+        assert "Day" in res["result"]["_meta"]["warnings"][0]
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "dates"}})
+        row = json.loads(res["result"]["content"][1]["text"])[0]
+        assert row["Day"] == "2024-02-29"
+        assert row["Stamp"] == "2023-04-01T13:45:00Z"
+
+        print("Testing BCD fields...")
+        bcd_fields = [{"name": "ID", "type": "LONG"}, {"name": "Amount", "type": "BCD", "decimals": 2}]
+        res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "amounts", "fields": bcd_fields}})
+        assert "Successfully created" in res["result"]["content"][0]["text"]
+        for rec in [{"ID": 1, "Amount": 12.5}, {"ID": 2, "Amount": "12345678901234567.89"}]:
+            res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "amounts", "record": rec}})
+            assert "warnings" not in res["result"].get("_meta", {})
+        res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "amounts", "record": {"ID": 3, "Amount": "1.234"}}})
+        assert "decimal places" in res["result"]["_meta"]["warnings"][0]
+        res = send_request(proc, "tools/call", {"name": "read_table_schema", "arguments": {"table_name": "amounts"}})
+        schema = {f["name"]: f for f in json.loads(res["result"]["content"][1]["text"])}
+        assert schema["Amount"]["decimals"] == 2
+        assert "decimals" not in schema["ID"]
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "amounts"}})
+        amounts = {r["ID"]: r["Amount"] for r in json.loads(res["result"]["content"][1]["text"])}
+        assert amounts == {1: 12.5, 2: "12345678901234567.89", 3: None}
+
+        print("Testing AUTOINC assignment...")
+        auto_fields = [{"name": "ID", "type": "AUTOINC"}, {"name": "Label", "type": "ALPHA", "length": 10}]
+        res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "autos", "fields": auto_fields}})
+        assert "Successfully created" in res["result"]["content"][0]["text"]
+        res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "autos", "record": {"ID": 99, "Label": "first"}}})
+        assert res["result"]["_meta"]["autoinc_values"] == {"ID": 1}
+        res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "autos", "records": [{"Label": "second"}, {"Label": "third"}]}})
+        results = json.loads(res["result"]["content"][-1]["text"])
+        assert [r["autoinc_values"]["ID"] for r in results] == [2, 3]
+
+        print("Testing create_table memory stability...")
+        leak_fields = [{"name": f"Field_with_a_long_name_{i:02d}", "type": "LONG"} for i in range(40)]
+        for i in range(20):
+            send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": f"warmup_{i}", "fields": leak_fields}})
+        before = rss_kb(proc)
+        for i in range(300):
+            res = send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": f"leak_{i}", "fields": leak_fields}})
+            assert "Successfully created" in res["result"]["content"][0]["text"]
+        growth = rss_kb(proc) - before
+        assert growth < 2048, f"RSS grew by {growth} kB over 300 creates"
 
         print("\nAll tests passed successfully! ✅")
 
@@ -101,5 +530,321 @@ def test_mcp_lifecycle():
             os.remove(os.path.join(location, f))
         os.rmdir(location)
 
+def test_search_threads():
+    """A search split across --search-threads returns matches in index order."""
+    location = "/tmp/paradox_test_threads"
+    os.makedirs(location, exist_ok=True)
+    # A scan cap beyond i32::MAX behaves as no cap.
+    proc = subprocess.Popen(["paradox-mcp", "--location", location, "--permit-editing", "--search-threads", "3",
+                             "--max-scan-records", str(2**32)],
+                            stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=subprocess.DEVNULL, text=True)
+    try:
+        print("Testing --search-threads...")
+        fields = [{"name": "ID", "type": "LONG"}, {"name": "Kind", "type": "ALPHA", "length": 10}]
+        send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "items", "fields": fields}})
+        records = [{"ID": i, "Kind": "even" if i % 2 == 0 else "odd"} for i in range(20)]
+        send_request(proc, "tools/call", {"name": "bulk_insert", "arguments": {"table_name": "items", "records": records}})
+        args = {"table_name": "items", "query": {"Kind": "even"}, "offset": 2, "limit": 5}
+        res = send_request(proc, "tools/call", {"name": "search_table", "arguments": args})
+        assert [r["ID"] for r in json.loads(res["result"]["content"][1]["text"])] == [4, 6, 8, 10, 12]
+        assert res["result"]["_meta"]["nextOffset"] == 7
+        assert res["result"]["_meta"]["complete"] is True
+    finally:
+        proc.terminate()
+        for f in os.listdir(location):
+            os.remove(os.path.join(location, f))
+        os.rmdir(location)
+
+def test_query_table():
+    """query_table filters, orders, pages, projects and aggregates."""
+    location = "/tmp/paradox_test_query"
+    os.makedirs(location, exist_ok=True)
+    proc = subprocess.Popen(["paradox-mcp", "--location", location, "--permit-editing"],
+                            stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=subprocess.DEVNULL, text=True)
+    try:
+        print("Testing query_table...")
+        fields = [{"name": "ID", "type": "LONG"}, {"name": "City", "type": "ALPHA", "length": 10},
+                  {"name": "Amount", "type": "NUMBER"}]
+        send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "orders", "fields": fields}})
+        records = [
+            {"ID": 1, "City": "Cluj", "Amount": 30.0},
+            {"ID": 2, "City": "Iasi", "Amount": 10.0},
+            {"ID": 3, "City": "Cluj", "Amount": 20.0},
+            {"ID": 4, "City": "Arad", "Amount": 40.0},
+            {"ID": 5, "City": "Cluj", "Amount": None},
+        ]
+        send_request(proc, "tools/call", {"name": "bulk_insert", "arguments": {"table_name": "orders", "records": records}})
+
+        def query(**arguments):
+            res = send_request(proc, "tools/call", {"name": "query_table", "arguments": {"table_name": "orders", **arguments}})
+            return json.loads(res["result"]["content"][1]["text"])
+
+        rows = query(order_by=["City", "Amount desc"], offset=1, limit=3, select=["ID"])
+        assert rows == [{"ID": 1}, {"ID": 3}, {"ID": 5}]
+        rows = query(filter={"City": "Cluj"}, select=["Amount", "ID"], limit=2)
+        assert rows == [{"Amount": 30.0, "ID": 1}, {"Amount": 20.0, "ID": 3}]
+        assert list(rows[0].keys()) == ["Amount", "ID"]
+        aggregates = query(filter={"City": "Cluj"}, aggregate=[
+            {"op": "count"}, {"op": "count", "field": "Amount"}, {"op": "sum", "field": "Amount"},
+            {"op": "avg", "field": "Amount"}, {"op": "min", "field": "Amount"}, {"op": "max", "field": "Amount"}])
+        assert aggregates == {"count": 3, "count(Amount)": 2, "sum(Amount)": 50.0, "avg(Amount)": 25.0,
+                              "min(Amount)": 20.0, "max(Amount)": 30.0}
+        res = send_request(proc, "tools/call", {"name": "query_table", "arguments": {"table_name": "orders", "aggregate": [{"op": "sum", "field": "City"}]}})
+        assert res["result"]["_meta"]["errorCode"] == "AGGREGATE_TYPE_MISMATCH"
+    finally:
+        proc.terminate()
+        for f in os.listdir(location):
+            os.remove(os.path.join(location, f))
+        os.rmdir(location)
+
+def test_table_busy():
+    """With --lock-timeout-ms 0, a call on a table another call holds fails fast with TABLE_BUSY."""
+    import http.client
+    import socket
+    location = "/tmp/paradox_test_busy"
+    os.makedirs(location, exist_ok=True)
+    port = 18765
+    proc = subprocess.Popen(["paradox-mcp", "--location", location, "--permit-editing", "--port", str(port), "--lock-timeout-ms", "0"],
+                            stdin=subprocess.DEVNULL, stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL)
+    try:
+        print("Testing TABLE_BUSY over SSE...")
+        for _ in range(50):
+            try:
+                stream = socket.create_connection(("127.0.0.1", port))
+                break
+            except OSError:
+                time.sleep(0.1)
+        stream.sendall(b"GET /sse HTTP/1.1\r\nHost: localhost:%d\r\n\r\n" % port)
+        events = stream.makefile("r")
+        endpoint = next(line for line in events if line.startswith("data: /messages"))[6:].strip()
+        replies = {}
+        arrived = threading.Condition()
+
+        def read_events():
+            for line in events:
+                if line.startswith("data: "):
+                    reply = json.loads(line[6:])
+                    with arrived:
+                        replies[reply["id"]] = reply
+                        arrived.notify_all()
+        threading.Thread(target=read_events, daemon=True).start()
+
+        def post(req_id, name, arguments):
+            body = json.dumps({"jsonrpc": "2.0", "id": req_id, "method": "tools/call", "params": {"name": name, "arguments": arguments}})
+            conn = http.client.HTTPConnection("127.0.0.1", port)
+            conn.request("POST", endpoint, body, {"Content-Type": "application/json"})
+            assert conn.getresponse().status == 202
+            conn.close()
+
+        # Requests that a rebound DNS name could send from a browser are refused.
+        for headers, status in [({"Host": "evil.example:%d" % port}, 403),
+                                ({"Origin": "http://evil.example"}, 403),
+                                ({"Origin": "http://localhost:%d" % port}, 200)]:
+            conn = http.client.HTTPConnection("127.0.0.1", port)
+            conn.request("GET", "/metrics", headers=headers)
+            assert conn.getresponse().status == status, headers
+            conn.close()
+
+        def wait_for(ids):
+            with arrived:
+                assert arrived.wait_for(lambda: all(i in replies for i in ids), timeout=60)
+
+        fields = [{"name": "ID", "type": "LONG"}, {"name": "Word", "type": "ALPHA", "length": 20}]
+        post(1, "create_table", {"table_name": "big", "fields": fields})
+        wait_for([1])
+        post(2, "bulk_insert", {"table_name": "big", "records": [{"ID": i, "Word": f"w{i * 7919 % 20000}"} for i in range(20000)]})
+        wait_for([2])
+
+        busy = False
+        for attempt in range(5):
+            base = 100 * (attempt + 1)
+            post(base, "query_table", {"table_name": "big", "order_by": ["Word desc"], "limit": 1})
+            ids = [base + k for k in range(1, 21)]
+            for req_id in ids:
+                post(req_id, "count_records", {"table_name": "big"})
+            wait_for([base] + ids)
+            codes = [replies[i]["result"].get("_meta", {}).get("errorCode") for i in ids]
+            if "TABLE_BUSY" in codes:
+                busy = True
+                break
+        assert busy
+        stream.close()
+    finally:
+        proc.terminate()
+        proc.wait()
+        for f in os.listdir(location):
+            os.remove(os.path.join(location, f))
+        os.rmdir(location)
+
+def test_handle_cache():
+    """Cached read handles never serve data older than the last write."""
+    location = "/tmp/paradox_test_cache"
+    os.makedirs(location, exist_ok=True)
+    proc = subprocess.Popen(["paradox-mcp", "--location", location, "--permit-editing", "--handle-cache-size", "4"],
+                            stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=subprocess.DEVNULL, text=True)
+    try:
+        print("Testing --handle-cache-size invalidation...")
+        fields = [{"name": "ID", "type": "LONG"}]
+        send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "cached", "fields": fields}})
+
+        def ids():
+            res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "cached"}})
+            return [r["ID"] for r in json.loads(res["result"]["content"][1]["text"])]
+
+        assert ids() == []
+        send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "cached", "record": {"ID": 1}}})
+        assert ids() == [1]
+        send_request(proc, "tools/call", {"name": "update_record", "arguments": {"table_name": "cached", "index": 0, "record": {"ID": 2}}})
+        assert ids() == [2]
+        send_request(proc, "tools/call", {"name": "bulk_insert", "arguments": {"table_name": "cached", "records": [{"ID": 3}]}})
+        res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_name": "cached"}})
+        assert json.loads(res["result"]["content"][1]["text"])["count"] == 2
+        send_request(proc, "tools/call", {"name": "truncate_table", "arguments": {"table_name": "cached", "confirm": True}})
+        assert ids() == []
+    finally:
+        proc.terminate()
+        for f in os.listdir(location):
+            os.remove(os.path.join(location, f))
+        os.rmdir(location)
+
+def test_read_limits():
+    """--default-limit applies without a limit; larger limits clamp to --max-limit."""
+    location = "/tmp/paradox_test_limits"
+    os.makedirs(location, exist_ok=True)
+    # A lock timeout too large for a deadline must not break locked calls.
+    proc = subprocess.Popen(["paradox-mcp", "--location", location, "--permit-editing", "--default-limit", "2", "--max-limit", "3",
+                             "--lock-timeout-ms", str(2**64 - 1)],
+                            stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=subprocess.DEVNULL, text=True)
+    try:
+        print("Testing --default-limit and --max-limit...")
+        fields = [{"name": "ID", "type": "LONG"}]
+        send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "rows", "fields": fields}})
+        send_request(proc, "tools/call", {"name": "bulk_insert", "arguments": {"table_name": "rows", "records": [{"ID": i} for i in range(5)]}})
+        for tool in ["read_table_data", "read_sorted"]:
+            for extra, expected in [({}, 2), ({"limit": 10}, 3), ({"limit": 1}, 1)]:
+                res = send_request(proc, "tools/call", {"name": tool, "arguments": {"table_name": "rows", **extra}})
+                assert len(json.loads(res["result"]["content"][1]["text"])) == expected, (tool, extra)
+    finally:
+        proc.terminate()
+        for f in os.listdir(location):
+            os.remove(os.path.join(location, f))
+        os.rmdir(location)
+
+    for flags in [["--default-limit", "0"], ["--default-limit", "5", "--max-limit", "4"]]:
+        proc = subprocess.run(["paradox-mcp", "--location", "/tmp", *flags], stdin=subprocess.DEVNULL,
+                              capture_output=True, text=True, timeout=10)
+        assert proc.returncode != 0, flags
+
+def test_encoding():
+    """ALPHA text is stored in the --encoding charset and read back intact."""
+    location = "/tmp/paradox_test_encoding"
+    os.makedirs(location, exist_ok=True)
+    proc = subprocess.Popen(
+        ["paradox-mcp", "--location", location, "--permit-editing", "--encoding", "cp850"],
+        stdin=subprocess.PIPE,
+        stdout=subprocess.PIPE,
+        stderr=subprocess.DEVNULL,
+        text=True
+    )
+    try:
+        print("Testing --encoding cp850...")
+        fields = [{"name": "Name", "type": "ALPHA", "length": 20}]
+        send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": "names", "fields": fields}})
+        res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "names", "record": {"Name": "Ärger café"}}})
+        assert "warnings" not in res["result"].get("_meta", {})
+        with open(os.path.join(location, "names.db"), "rb") as f:
+            assert "Ärger café".encode("cp850") in f.read()
+        res = send_request(proc, "tools/call", {"name": "read_table_data", "arguments": {"table_name": "names"}})
+        assert json.loads(res["result"]["content"][1]["text"])[0]["Name"] == "Ärger café"
+        res = send_request(proc, "tools/call", {"name": "insert_record", "arguments": {"table_name": "names", "record": {"Name": "ș"}}})
+        assert "cp850" in res["result"]["_meta"]["warnings"][0]
+        res = send_request(proc, "tools/call", {"name": "import_csv", "arguments": {"table_name": "names", "csv_text": "Name\nCafé\nș\n"}})
+        report = json.loads(res["result"]["content"][1]["text"])
+        assert report["inserted"] == 1
+        assert [s["line"] for s in report["skipped"]] == [3]
+        assert "cp850" in report["skipped"][0]["error"]
+    finally:
+        proc.terminate()
+        for f in os.listdir(location):
+            os.remove(os.path.join(location, f))
+        os.rmdir(location)
+
+def test_multiple_locations():
+    """Tables from every --location are listed; the first location wins."""
+    first, second = "/tmp/paradox_test_first", "/tmp/paradox_test_second"
+    for location, field in [(second, "Second"), (first, "First")]:
+        os.makedirs(location, exist_ok=True)
+        proc = subprocess.Popen(["paradox-mcp", "--location", location, "--permit-editing"],
+                                stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=subprocess.DEVNULL, text=True)
+        for table in ["shared", field.lower()]:
+            send_request(proc, "tools/call", {"name": "create_table", "arguments": {"table_name": table, "fields": [{"name": field, "type": "LONG"}]}})
+        proc.terminate()
+        proc.wait()
+
+    proc = subprocess.Popen(["paradox-mcp", "--location", first, "--location", second],
+                            stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=subprocess.DEVNULL, text=True)
+    try:
+        print("Testing multiple locations...")
+        res = send_request(proc, "tools/call", {"name": "list_tables"})
+        tables = {t["name"]: t for t in res["result"]["_meta"]["tables"]}
+        assert sorted(tables) == ["first.db", "second.db", "shared.db"]
+        assert tables["shared.db"]["path"] == os.path.join(first, "shared.db")
+        assert tables["shared.db"]["alsoIn"] == [os.path.join(second, "shared.db")]
+        res = send_request(proc, "tools/call", {"name": "read_table_schema", "arguments": {"table_name": "shared"}})
+        assert json.loads(res["result"]["content"][1]["text"])[0]["name"] == "First"
+        res = send_request(proc, "tools/call", {"name": "count_records", "arguments": {"table_name": "second"}})
+        assert "isError" not in res["result"]
+    finally:
+        proc.terminate()
+        for location in [first, second]:
+            for f in os.listdir(location):
+                os.remove(os.path.join(location, f))
+            os.rmdir(location)
+
+def test_invalid_location():
+    """A missing or non-directory --location stops startup with an error."""
+    for location in ["/tmp/paradox_test_missing_dir", "/etc/hostname"]:
+        res = subprocess.run(["paradox-mcp", "--location", location], stdin=subprocess.DEVNULL,
+                             capture_output=True, text=True, timeout=10)
+        assert res.returncode != 0
+        assert location in res.stderr
+
+def test_lsp_framing():
+    """Content-Length framing answers in kind; frames with a huge or missing
+    length get a parse error and the session goes on."""
+    proc = subprocess.Popen(["paradox-mcp", "--location", "/tmp", "--framing", "lsp"],
+                            stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=subprocess.DEVNULL)
+    print("Testing --framing lsp...")
+    body = json.dumps({"jsonrpc": "2.0", "id": 1, "method": "ping"}).encode()
+    proc.stdin.write(b"Content-Length: %d\r\n\r\n%s" % (len(body), body))
+    proc.stdin.write(b"Content-Type: application/json\r\n\r\n")
+    proc.stdin.write(b"Content-Length: %d\r\n\r\n%s" % (len(body), body))
+    proc.stdin.write(b"Content-Length: 99999999999999\r\n\r\n{}")
+    proc.stdin.close()
+    replies = []
+    while True:
+        header = proc.stdout.readline()
+        if not header:
+            break
+        length = int(header.split(b":")[1])
+        proc.stdout.readline()
+        replies.append(json.loads(proc.stdout.read(length)))
+    proc.wait(timeout=5)
+    assert proc.returncode == 0
+    assert len(replies) == 4
+    assert replies[0]["id"] == 1 and "result" in replies[0]
+    assert replies[1]["error"]["code"] == -32700
+    assert replies[2]["id"] == 1 and "result" in replies[2]
+    assert replies[3]["error"]["code"] == -32700
+
 if __name__ == "__main__":
     test_mcp_lifecycle()
+    test_search_threads()
+    test_query_table()
+    test_table_busy()
+    test_handle_cache()
+    test_read_limits()
+    test_encoding()
+    test_multiple_locations()
+    test_invalid_location()
+    test_lsp_framing()